@@ -0,0 +1,301 @@
+use anyhow::Result;
+use std::{
+  cell::RefCell,
+  collections::HashMap,
+  future::Future,
+  pin::Pin,
+  rc::Rc,
+  task::{Poll, Waker},
+  time::Duration,
+};
+
+use super::{
+  config, error,
+  executor::PollFn,
+  node::{drop_reply, forwarded, others},
+  quorum::fan_out,
+  Error, ErrorCode, Executor, Handler, Message, MsgBody, Node, NodeID, Outbox, Tally, TimerId,
+};
+
+// a workload written with `async fn`s, RPCs and sleeps are awaited inline
+// everything runs on the event loop thread, many handlers can be in flight at
+// once but only one runs at a time, so state only needs a `RefCell`
+// not `Send`, hence no bounds on the futures
+#[allow(async_fn_in_trait)]
+pub trait AsyncHandler: Sized + 'static {
+  async fn init(&self, _ctx: &AsyncContext) -> Result<()> {
+    Ok(())
+  }
+
+  async fn handle(&self, ctx: &AsyncContext, msg: Message) -> Result<()>;
+}
+
+enum Slot {
+  Waiting(Option<Waker>),
+  // the reply, `None` if it timed out
+  Done(Option<Box<Message>>),
+}
+
+#[derive(Default)]
+struct Waits {
+  slots: HashMap<u64, Slot>,
+  // timeouts to set for slots
+  timers: Vec<(Duration, u64)>,
+  spawned: Vec<Pin<Box<dyn Future<Output = ()>>>>,
+}
+
+// what an `AsyncHandler` gets to talk to other nodes
+#[derive(Clone)]
+pub struct AsyncContext {
+  pub id: NodeID,
+  pub node_ids: Vec<NodeID>,
+  outbox: Outbox,
+  waits: Rc<RefCell<Waits>>,
+  rpc_timeout: Duration,
+}
+
+impl AsyncContext {
+  pub fn other_nodes(&self) -> Vec<NodeID> {
+    others(&self.id, &self.node_ids)
+  }
+
+  pub fn gen_id(&self) -> u64 {
+    self.outbox.gen_id()
+  }
+
+  pub fn send(&self, dest: &str, body: MsgBody) -> Result<()> {
+    self.outbox.send_from(&self.id, dest, body)
+  }
+
+  // see `Outbox::reply`
+  pub fn reply(&self, origin: &Message, resp_body: MsgBody) -> Result<()> {
+    self.outbox.reply(origin, resp_body)
+  }
+
+  // sends `body` and resolves to the reply, `error` replies included,
+  // a timeout is an `ErrorCode::Timeout` error
+  pub async fn rpc(&self, dest: &str, mut body: MsgBody) -> Result<Message> {
+    let id = self.gen_id();
+    body.msg_id = Some(id);
+    self.wait_for(id, self.rpc_timeout);
+    self.send(dest, body)?;
+
+    match self.wait(id).await {
+      Some(reply) => Ok(reply),
+      None => Err(Error::new(ErrorCode::Timeout, format!("no reply from {}", dest)).into()),
+    }
+  }
+
+  // like `rpc`, with `error` replies as `Error`s
+  pub async fn call(&self, dest: &str, body: MsgBody) -> Result<Message> {
+    let reply = self.rpc(dest, body).await?;
+    match Error::from_reply(&reply.body) {
+      Some(e) => Err(e.into()),
+      None => Ok(reply),
+    }
+  }
+
+  // sends `body` to each of `nodes` and resolves to the successful replies once
+  // `need` of them are in, or an error once that can't happen within the RPC timeout
+  // later replies are ignored
+  pub async fn quorum(&self, nodes: &[NodeID], body: MsgBody, need: usize) -> Result<Vec<Message>> {
+    let mut pending = fan_out(&self.outbox, &self.id, nodes, &body, |id| {
+      self.wait_for(id, self.rpc_timeout)
+    })?;
+
+    let mut tally = Tally::new(need, nodes.len());
+    let outcome = loop {
+      if let Some(outcome) = tally.outcome() {
+        break outcome;
+      }
+      let (id, reply) = self.wait_any(&pending).await;
+      pending.retain(|p| *p != id);
+      tally.add(reply);
+    };
+
+    let mut w = self.waits.borrow_mut();
+    for id in pending {
+      w.slots.remove(&id);
+    }
+    outcome.map_err(Into::into)
+  }
+
+  // hands `msg` to `to` and relays its reply back to the sender as ours,
+  // no reply within the RPC timeout is answered with temporarily-unavailable
+  pub async fn forward(&self, msg: &Message, to: &str) -> Result<()> {
+    let rpc = self.rpc(to, msg.body.clone()).await;
+    forwarded(&self.outbox, msg, to, rpc)
+  }
+
+  pub async fn sleep(&self, d: Duration) {
+    let id = self.gen_id();
+    self.wait_for(id, d);
+    self.wait(id).await;
+  }
+
+  // runs `fut` alongside the handlers, e.g. a loop that gossips and sleeps
+  pub fn spawn(&self, fut: impl Future<Output = Result<()>> + 'static) {
+    self.waits.borrow_mut().spawned.push(Box::pin(log_error("task", fut)));
+  }
+
+  fn wait_for(&self, id: u64, timeout: Duration) {
+    let mut w = self.waits.borrow_mut();
+    w.slots.insert(id, Slot::Waiting(None));
+    w.timers.push((timeout, id));
+  }
+
+  async fn wait(&self, id: u64) -> Option<Message> {
+    self.wait_any(&[id]).await.1
+  }
+
+  // resolves to the first of `ids` to get a reply or time out
+  fn wait_any(&self, ids: &[u64]) -> impl Future<Output = (u64, Option<Message>)> {
+    let waits = self.waits.clone();
+    let ids = ids.to_vec();
+    PollFn(move |cx: &mut std::task::Context| {
+      let mut w = waits.borrow_mut();
+      let done = ids
+        .iter()
+        .copied()
+        .find(|id| !matches!(w.slots.get(id), Some(Slot::Waiting(_))));
+      if let Some(id) = done {
+        let reply = match w.slots.remove(&id) {
+          Some(Slot::Done(reply)) => reply.map(|r| *r),
+          _ => None,
+        };
+        return Poll::Ready((id, reply));
+      }
+
+      for id in &ids {
+        if let Some(Slot::Waiting(waker)) = w.slots.get_mut(id) {
+          *waker = Some(cx.waker().clone());
+        }
+      }
+      Poll::Pending
+    })
+  }
+}
+
+async fn log_error(what: &str, fut: impl Future<Output = Result<()>>) {
+  if let Err(e) = fut.await {
+    error!("Failed handling {}: {}", what, e);
+  }
+}
+
+// runs an `AsyncHandler` as a `Handler` on a built-in executor, the event loop
+// polls tasks as replies arrive and timeouts fire
+// RPCs time out after `--rpc-timeout` ms, 1000 by default
+pub struct Async<H> {
+  handler: Rc<H>,
+  rpc_timeout: Duration,
+  executor: Executor,
+  waits: Rc<RefCell<Waits>>,
+  timers: HashMap<u64, TimerId>,
+  ctx: Option<AsyncContext>,
+}
+
+impl<H: AsyncHandler> Async<H> {
+  pub fn new(handler: H) -> Self {
+    Async {
+      handler: Rc::new(handler),
+      rpc_timeout: Duration::from_secs(1),
+      executor: Default::default(),
+      waits: Default::default(),
+      timers: HashMap::new(),
+      ctx: None,
+    }
+  }
+
+  pub fn from_config(handler: H) -> Result<Self> {
+    let mut a = Async::new(handler);
+    a.rpc_timeout = Duration::from_millis(config::parse_or("rpc-timeout", 1_000)?);
+    Ok(a)
+  }
+
+  // polls tasks until they're all waiting, then sets the timeouts they asked for
+  fn run(&mut self, node: &mut Node<Self>) {
+    loop {
+      self.executor.run_ready();
+
+      let (timers, spawned) = {
+        let mut w = self.waits.borrow_mut();
+        (std::mem::take(&mut w.timers), std::mem::take(&mut w.spawned))
+      };
+      if timers.is_empty() && spawned.is_empty() {
+        return;
+      }
+
+      for fut in spawned {
+        self.executor.spawn(fut);
+      }
+      for (after, id) in timers {
+        let timer = node.set_timeout(after, move |s, node| {
+          s.timers.remove(&id);
+          s.complete(id, None);
+          s.run(node);
+          Ok(())
+        });
+        self.timers.insert(id, timer);
+      }
+    }
+  }
+
+  fn complete(&mut self, id: u64, reply: Option<Message>) -> bool {
+    let mut w = self.waits.borrow_mut();
+    let waker = match w.slots.get_mut(&id) {
+      Some(Slot::Waiting(waker)) => waker.take(),
+      _ => return false,
+    };
+    w.slots.insert(id, Slot::Done(reply.map(Box::new)));
+    if let Some(waker) = waker {
+      waker.wake();
+    }
+    true
+  }
+}
+
+impl<H: AsyncHandler> Handler for Async<H> {
+  fn init(&mut self, node: &mut Node<Self>) -> Result<()> {
+    node.settles_requests = true;
+    let ctx = AsyncContext {
+      id: node.id.clone(),
+      node_ids: node.node_ids.clone(),
+      outbox: node.outbox(),
+      waits: self.waits.clone(),
+      rpc_timeout: self.rpc_timeout,
+    };
+    self.ctx = Some(ctx.clone());
+
+    let handler = self.handler.clone();
+    self
+      .executor
+      .spawn(log_error("init", async move { handler.init(&ctx).await }));
+    self.run(node);
+    Ok(())
+  }
+
+  fn handle(&mut self, node: &mut Node<Self>, msg: Message) -> Result<()> {
+    let (handler, ctx) = (self.handler.clone(), self.ctx.clone().unwrap());
+    self.executor.spawn(async move {
+      let (typ, src, id) = (msg.body.typ.clone(), msg.src.clone(), msg.body.msg_id);
+      let outbox = ctx.outbox.clone();
+      log_error(&typ, async move { handler.handle(&ctx, msg).await }).await;
+      outbox.settle(&src, id);
+    });
+    self.run(node);
+    Ok(())
+  }
+
+  fn handle_reply(&mut self, node: &mut Node<Self>, msg: Message) -> Result<()> {
+    let id = msg.body.in_reply_to.unwrap();
+    if !self.waits.borrow().slots.contains_key(&id) {
+      drop_reply(&msg);
+    } else if self.complete(id, Some(msg)) {
+      if let Some(timer) = self.timers.remove(&id) {
+        node.clear_timer(timer);
+      }
+    }
+    self.run(node);
+    Ok(())
+  }
+}
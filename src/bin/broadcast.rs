@@ -1,146 +1,371 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use rand::seq::SliceRandom;
+use serde_json::Value as Jval;
 use std::{
   collections::{HashMap, HashSet},
-  io::{self, Write},
-  sync::{
-    atomic::{AtomicU64, Ordering},
-    mpsc::{self, Sender},
-  },
-  thread,
-  time::Duration,
+  str::FromStr,
+  time::{Duration, Instant},
 };
 
 use maelstrom::*;
 
-fn main() -> Result<()> {
-  // logging
-  let stderr = io::stderr();
-  let mut log = stderr.lock();
-
-  // input
-  let stdin = io::stdin();
-
-  // msg_id generation
-  let msg_id = AtomicU64::new(0);
-  let gen_id = move || Some(msg_id.fetch_add(1, Ordering::SeqCst));
-
-  // node data
-  let mut node_id = String::new();
-  let mut neighbors: Vec<String> = vec![];
-  let mut messages: HashSet<u64> = HashSet::new();
-
-  // track retry threads per msg_id (u64)
-  let mut gossiping: HashMap<u64, Sender<()>> = HashMap::new();
-
-  loop {
-    let mut input = String::new();
-    if let Ok(_) = stdin.read_line(&mut input) {
-      // log.write_all(format!("Raw message: {}\n", input.trim()).as_bytes())?;
-      let msg: Message = serde_json::from_str(&input)?;
-      // log.write_all(format!("Parse result: {:?}\n", &msg).as_bytes())?;
-
-      match msg.body.typ.as_str() {
-        "init" => {
-          node_id = msg.body.node_id.clone().unwrap();
-          log.write_all(format!("Node {} initialized\n", &node_id).as_bytes())?;
-
-          let r = MsgBody {
-            typ: "init_ok".to_owned(),
-            msg_id: gen_id(),
-            ..Default::default()
-          };
-          reply(&msg, r)?;
+// how often unacked batches are checked for retransmission
+const RETRY_TICK: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Strategy {
+  // push new values to every neighbor
+  Flood,
+  // push along a spanning tree, announce to the other neighbors
+  Plumtree,
+}
+
+impl FromStr for Strategy {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "flood" => Ok(Strategy::Flood),
+      "plumtree" => Ok(Strategy::Plumtree),
+      _ => Err(anyhow!("expected flood or plumtree")),
+    }
+  }
+}
+
+// values waiting for the next flush, per neighbor
+#[derive(Default)]
+struct Queues {
+  // pushed in acknowledged `gossip` batches
+  eager: HashMap<String, Vec<CanonicalValue>>,
+  // announced in `ihave` batches
+  lazy: HashMap<String, Vec<CanonicalValue>>,
+}
+
+impl Queues {
+  fn apply<S>(&mut self, node: &mut Node<S>, sends: Vec<PlumtreeSend<CanonicalValue>>) -> Result<()> {
+    for s in sends {
+      match s {
+        PlumtreeSend::Eager(dest, value) => self.eager.entry(dest).or_default().push(value),
+        PlumtreeSend::IHave(dest, value) => self.lazy.entry(dest).or_default().push(value),
+        PlumtreeSend::Graft(dest, values) => node.send(&dest, values_body("graft", values))?,
+        PlumtreeSend::Prune(dest) => node.send(&dest, values_body("prune", vec![]))?,
+      }
+    }
+
+    Ok(())
+  }
+}
+
+struct Broadcast {
+  // new values are queued per neighbor and flushed as one batch every window
+  batch_window: Duration,
+  // gossip graph, Maelstrom's topology or one built from the node ids
+  overlay: Overlay,
+  // which of the neighbors get new values
+  strategy: Strategy,
+  graft_timeout: Duration,
+  // how often to reconcile with a random node, to recover values missed during partitions
+  sync_interval: Duration,
+
+  neighbors: Vec<String>,
+  messages: HashSet<CanonicalValue>,
+  queues: Queues,
+  plumtree: Plumtree<CanonicalValue>,
+  // unacked batches
+  gossiping: Retransmitter,
+}
+
+impl Broadcast {
+  fn set_neighbors(&mut self, neighbors: Vec<String>) {
+    info!("Neighbors in {:?} overlay: {:?}", self.overlay, &neighbors);
+    self.plumtree = Plumtree::new(neighbors.clone(), self.graft_timeout);
+    self.neighbors = neighbors;
+  }
+
+  fn flush(&mut self, node: &mut Node<Self>) -> Result<()> {
+    let grafts = self.plumtree.poll(Instant::now());
+    self.queues.apply(node, grafts)?;
+
+    for (dest, batch) in self.queues.eager.drain().filter(|(_, batch)| !batch.is_empty()) {
+      let mut body = values_body("gossip", batch);
+      body.msg_id = Some(node.gen_id());
+      let gossip = Message {
+        src: node.id.clone(),
+        dest,
+        body,
+      };
+
+      node.send_msg(&gossip)?;
+      self.gossiping.track(gossip, Instant::now());
+    }
+
+    for (dest, batch) in self.queues.lazy.drain().filter(|(_, batch)| !batch.is_empty()) {
+      node.send(&dest, values_body("ihave", batch))?;
+    }
+
+    Ok(())
+  }
+
+  fn sync(&self, node: &mut Node<Self>, dest: &str) -> Result<()> {
+    let bd = MsgBody {
+      typ: "sync".to_owned(),
+      digest: Some(Digest::of(&self.messages).summary()),
+      ..Default::default()
+    };
+    node.send(dest, bd)
+  }
+}
+
+impl Handler for Broadcast {
+  fn init(&mut self, node: &mut Node<Self>) -> Result<()> {
+    if !self.overlay.uses_topology() {
+      self.set_neighbors(self.overlay.neighbors(&node.id, &node.node_ids, None));
+    }
+
+    // batches to suspected peers stay pending, backing off, until they're back
+    node.set_interval(RETRY_TICK, |s, node| {
+      for gossip in s.gossiping.poll(Instant::now()) {
+        if !node.is_suspected(&gossip.dest) {
+          node.send_msg(&gossip)?;
         }
-        "topology" => {
-          // parse neighbors
-          neighbors = msg.body.topology.clone().unwrap().get(&node_id).unwrap().clone();
-          log.write_all(format!("Neighbors: {:?}\n", &neighbors).as_bytes())?;
-
-          let r = MsgBody {
-            typ: "topology_ok".to_owned(),
-            msg_id: gen_id(),
-            ..Default::default()
-          };
-          reply(&msg, r)?;
+      }
+      Ok(())
+    });
+
+    node.set_interval(self.batch_window, |s, node| s.flush(node));
+    // don't leave queued values unsent
+    node.on_shutdown(|s, node| s.flush(node));
+
+    // anti-entropy, pulls what we miss from a random node
+    node.set_interval(self.sync_interval, |s, node| {
+      match node.live_nodes().choose(&mut rand::thread_rng()) {
+        Some(dest) => s.sync(node, dest),
+        None => Ok(()),
+      }
+    });
+    // and right away from peers that were suspected, they likely missed a lot
+    node.on_suspicion(|s, node, peer, suspected| if suspected { Ok(()) } else { s.sync(node, peer) });
+
+    Ok(())
+  }
+
+  fn handle(&mut self, node: &mut Node<Self>, msg: Message) -> Result<()> {
+    match msg.body.typ.as_str() {
+      "topology" => {
+        // parse neighbors
+        if self.overlay.uses_topology() {
+          self.set_neighbors(
+            self
+              .overlay
+              .neighbors(&node.id, &node.node_ids, msg.body.topology.as_ref()),
+          );
         }
-        "broadcast" => {
-          let msg_content = msg.body.message.clone().unwrap().as_u64().unwrap();
-          if messages.insert(msg_content) {
-            // new message, gossip
-            for nb in neighbors.iter().filter(|nb| *nb != &msg.src) {
-              let (tx, rx) = mpsc::channel();
-              let msg_id = gen_id();
-              let src = node_id.clone();
-              let dest = nb.clone();
-
-              thread::spawn(move || {
-                loop {
-                  let gossip = Message {
-                    src: src.clone(),
-                    dest: dest.clone(),
-                    body: MsgBody {
-                      typ: "broadcast".to_owned(),
-                      msg_id,
-                      message: Some(serde_json::Value::Number(serde_json::Number::from(msg_content))),
-                      ..Default::default()
-                    },
-                  };
-                  println!("{}", serde_json::to_string(&gossip).unwrap());
-
-                  // wait for response
-                  if let Ok(_) = rx.recv_timeout(Duration::from_millis(500)) {
-                    break;
-                  }
-                }
-              });
 
-              gossiping.insert(msg_id.unwrap(), tx);
+        let r = MsgBody {
+          typ: "topology_ok".to_owned(),
+          ..Default::default()
+        };
+        node.reply(&msg, r)
+      }
+      "broadcast" | "gossip" => {
+        let values: Vec<CanonicalValue> = match msg.body.typ.as_str() {
+          "broadcast" => vec![msg.body.message.clone().unwrap().into()],
+          _ => body_values(&msg.body),
+        };
+
+        let messages = &mut self.messages;
+        let new: Vec<CanonicalValue> = values.into_iter().filter(|x| messages.insert(x.clone())).collect();
+        match self.strategy {
+          Strategy::Flood => {
+            // queue new values for neighbors, except the one we got them from
+            if !new.is_empty() {
+              for nb in self.neighbors.iter().filter(|nb| *nb != &msg.src) {
+                self
+                  .queues
+                  .eager
+                  .entry(nb.clone())
+                  .or_default()
+                  .extend(new.iter().cloned());
+              }
+            }
+          }
+          Strategy::Plumtree => {
+            let from = if msg.body.typ == "gossip" {
+              Some(msg.src.as_str())
+            } else {
+              None
+            };
+
+            match from {
+              Some(from) if new.is_empty() => {
+                let sends = self.plumtree.redundant(from);
+                self.queues.apply(node, sends)?;
+              }
+              _ => {
+                for x in new {
+                  let sends = self.plumtree.deliver(x, from);
+                  self.queues.apply(node, sends)?;
+                }
+              }
             }
           }
+        }
+
+        let r = MsgBody {
+          typ: format!("{}_ok", msg.body.typ),
+          ..Default::default()
+        };
+        node.reply(&msg, r)
+      }
+      "ihave" => {
+        for x in body_values(&msg.body) {
+          if !self.messages.contains(&x) {
+            self.plumtree.ihave(x, &msg.src, Instant::now());
+          }
+        }
+        Ok(())
+      }
+      "graft" => {
+        self.plumtree.graft(&msg.src);
 
-          let r = MsgBody {
-            typ: "broadcast_ok".to_owned(),
-            msg_id: gen_id(),
+        let messages = &self.messages;
+        let wanted = body_values(&msg.body).into_iter().filter(|x| messages.contains(x));
+        self.queues.eager.entry(msg.src.clone()).or_default().extend(wanted);
+        Ok(())
+      }
+      "prune" => {
+        self.plumtree.prune(&msg.src);
+        Ok(())
+      }
+      "sync" => {
+        let theirs = msg.body.digest.clone().unwrap();
+        let ours = Digest::of(&self.messages);
+        if !ours.matches(&theirs) {
+          let bd = MsgBody {
+            typ: "sync_buckets".to_owned(),
+            digest: Some(ours),
             ..Default::default()
           };
-          reply(&msg, r)?;
+          node.send(&msg.src, bd)?;
         }
-        "broadcast_ok" => {
-          // cancel gossip thread
-          if let Some(tx) = gossiping.remove(&msg.body.in_reply_to.unwrap()) {
-            tx.send(())?;
-          }
+        Ok(())
+      }
+      "sync_buckets" => {
+        // send what we have in the differing buckets, so they can tell exactly what we miss
+        let theirs = msg.body.digest.clone().unwrap();
+        let buckets = Digest::of(&self.messages).diff(&theirs);
+        let bd = MsgBody {
+          typ: "sync_elements".to_owned(),
+          set: Some(in_buckets(&self.messages, &buckets)),
+          buckets: Some(buckets),
+          ..Default::default()
+        };
+        node.send(&msg.src, bd)
+      }
+      "sync_elements" => {
+        let buckets = msg.body.buckets.clone().unwrap();
+        let theirs = msg.body.set.clone().unwrap();
+
+        let missing: HashSet<CanonicalValue> = in_buckets(&self.messages, &buckets)
+          .difference(&theirs)
+          .cloned()
+          .collect();
+        // they've sent theirs anyway
+        for x in theirs {
+          self.plumtree.have(&x);
+          self.messages.insert(x);
         }
-        "read" => {
-          let r = MsgBody {
-            typ: "read_ok".to_owned(),
-            msg_id: gen_id(),
-            messages: Some(
-              messages
-                .iter()
-                .map(|x| serde_json::Value::Number(serde_json::Number::from(*x)))
-                .collect(),
-            ),
+
+        if !missing.is_empty() {
+          let bd = MsgBody {
+            typ: "sync_missing".to_owned(),
+            set: Some(missing),
             ..Default::default()
           };
-          reply(&msg, r)?;
+          node.send(&msg.src, bd)?;
+        }
+        Ok(())
+      }
+      "sync_missing" => {
+        for x in msg.body.set.clone().unwrap() {
+          self.plumtree.have(&x);
+          self.messages.insert(x);
         }
-        _ => unimplemented!("unexpected message"),
+        Ok(())
+      }
+      "read" => {
+        let r = MsgBody {
+          typ: "read_ok".to_owned(),
+          messages: Some(self.messages.iter().map(|x| x.value().clone()).collect()),
+          ..Default::default()
+        };
+        node.reply(&msg, r)
+      }
+      _ => {
+        warn!("Ignoring unexpected {} from {}", msg.body.typ, msg.src);
+        Ok(())
       }
     }
   }
+
+  // acks of gossip batches, tracked by msg id rather than with callbacks as
+  // retransmissions reuse it
+  fn handle_reply(&mut self, _node: &mut Node<Self>, msg: Message) -> Result<()> {
+    match msg.body.typ.as_str() {
+      "gossip_ok" => {
+        self.gossiping.ack(msg.body.in_reply_to.unwrap());
+      }
+      // e.g. a peer that wasn't initialized yet, the batch is retried
+      "error" => warn!(
+        "Ignoring error from {}: {}",
+        msg.src,
+        msg.body.text.as_deref().unwrap_or_default()
+      ),
+      _ => debug!("Ignoring {} from {}", msg.body.typ, msg.src),
+    }
+    Ok(())
+  }
 }
 
-fn reply(origin: &Message, mut resp_body: MsgBody) -> Result<()> {
-  resp_body.in_reply_to = Some(origin.body.msg_id.unwrap());
+fn main() -> Result<()> {
+  let graft_timeout = Duration::from_millis(config::parse_or("graft-timeout", 1_000)?);
+
+  Node::run(Broadcast {
+    batch_window: config::interval("batch-window", 100)?,
+    overlay: config::parse_or("overlay", Overlay::Given)?,
+    strategy: config::parse_or("strategy", Strategy::Flood)?,
+    graft_timeout,
+    sync_interval: config::interval("sync-interval", 1_000)?,
+    neighbors: vec![],
+    messages: HashSet::new(),
+    queues: Default::default(),
+    plumtree: Plumtree::new(vec![], graft_timeout),
+    gossiping: Default::default(),
+  })
+}
 
-  let reply = Message {
-    src: origin.dest.clone(),
-    dest: origin.src.clone(),
-    body: resp_body,
-  };
+fn in_buckets(messages: &HashSet<CanonicalValue>, buckets: &[usize]) -> HashSet<CanonicalValue> {
+  messages
+    .iter()
+    .filter(|x| buckets.contains(&bucket_of(*x)))
+    .cloned()
+    .collect()
+}
 
-  println!("{}", serde_json::to_string(&reply)?);
+fn values_body(typ: &str, values: Vec<CanonicalValue>) -> MsgBody {
+  MsgBody {
+    typ: typ.to_owned(),
+    messages: Some(values.into_iter().map(Jval::from).collect()),
+    ..Default::default()
+  }
+}
 
-  Ok(())
+fn body_values(body: &MsgBody) -> Vec<CanonicalValue> {
+  body
+    .messages
+    .clone()
+    .unwrap_or_default()
+    .into_iter()
+    .map(CanonicalValue::from)
+    .collect()
 }
@@ -1,14 +1,5 @@
 use anyhow::Result;
-use std::{
-  collections::HashMap,
-  io::{self, Write},
-  sync::{
-    atomic::{AtomicU64, Ordering},
-    Arc, RwLock,
-  },
-  thread,
-  time::Duration,
-};
+use std::collections::HashMap;
 
 use maelstrom::*;
 
@@ -22,33 +13,31 @@ impl CRDT for GCounter {
     GCounter(HashMap::new())
   }
 
-  fn add(&mut self, (node, incr): Self::Element) {
-    if let Some(val) = self.0.get_mut(&node) {
-      *val += incr;
-    } else {
-      self.0.insert(node, incr);
-    }
+  fn add(&mut self, (node, incr): Self::Element) -> Result<(), Error> {
+    let val = self.0.entry(node).or_default();
+    *val = val
+      .checked_add(incr)
+      .ok_or_else(|| Error::new(ErrorCode::Abort, "counter overflow"))?;
+    Ok(())
   }
 
-  fn read(&self) -> Self::Value {
-    self.0.values().sum()
+  fn read(&self) -> Result<Self::Value, Error> {
+    self
+      .0
+      .values()
+      .try_fold(0u64, |acc, v| acc.checked_add(*v))
+      .ok_or_else(|| Error::new(ErrorCode::Abort, "counter value out of u64 range"))
   }
 
   fn merge(&mut self, other: &Self) {
-    for (ok, ov) in other.0.iter() {
-      if let Some(val) = self.0.get_mut(ok) {
-        *val = (*val).max(*ov);
-      } else {
-        self.0.insert(ok.clone(), ov.clone());
-      }
-    }
+    merge_max(&mut self.0, &other.0);
   }
 
   fn from_msg_body(mb: &MsgBody) -> Self {
     GCounter(mb.counters.clone().unwrap())
   }
 
-  fn into_msg_body(&self) -> MsgBody {
+  fn to_msg_body(&self) -> MsgBody {
     MsgBody {
       counters: Some(self.0.clone()),
       ..Default::default()
@@ -56,116 +45,60 @@ impl CRDT for GCounter {
   }
 }
 
-fn main() -> Result<()> {
-  // logging
-  let stderr = io::stderr();
-  let mut log = stderr.lock();
-
-  // input
-  let stdin = io::stdin();
-
-  // msg_id generation
-  let msg_id = AtomicU64::new(0);
-  let gen_id = move || Some(msg_id.fetch_add(1, Ordering::SeqCst));
-
-  // node data
-  let mut node_id = String::new();
-  let crdt = Arc::new(RwLock::new(GCounter::init()));
-
-  loop {
-    let mut input = String::new();
-    if let Ok(_) = stdin.read_line(&mut input) {
-      // log.write_all(format!("Raw message: {}\n", input.trim()).as_bytes())?;
-      let msg: Message = serde_json::from_str(&input)?;
-      // log.write_all(format!("Parse result: {:?}\n", &msg).as_bytes())?;
-
-      match msg.body.typ.as_str() {
-        "init" => {
-          node_id = msg.body.node_id.clone().unwrap();
-          let other_nodes: Vec<String> = msg
-            .body
-            .node_ids
-            .clone()
-            .unwrap()
-            .into_iter()
-            .filter(|n| n != &node_id)
-            .collect();
-
-          log.write_all(format!("Node {} initialized\n", &node_id).as_bytes())?;
-
-          // replicate thread
-          let set_reader = crdt.clone();
-          let ni = node_id.clone();
-          thread::spawn(move || loop {
-            thread::sleep(Duration::from_millis(2_000));
-
-            let mut bd = set_reader.read().unwrap().into_msg_body();
-            bd.typ = "replicate".to_owned();
-
-            for dest in other_nodes.iter().cloned() {
-              let msg = Message {
-                src: ni.clone(),
-                dest,
-                body: bd.clone(),
-              };
-
-              println!("{}", serde_json::to_string(&msg).unwrap());
+struct GCounterNode {
+  crdt: GCounter,
+  // replication schedule
+  gossip: GossipConfig,
+}
+
+impl Handler for GCounterNode {
+  fn init(&mut self, node: &mut Node<Self>) -> Result<()> {
+    node.set_interval(self.gossip.interval, |s, node| s.gossip.gossip(node, &s.crdt));
+
+    Ok(())
+  }
+
+  fn handle(&mut self, node: &mut Node<Self>, msg: Message) -> Result<()> {
+    match msg.body.typ.as_str() {
+      "add" => {
+        let r = match msg.body.delta.as_ref().and_then(|d| d.as_u64()) {
+          Some(delta) => match self.crdt.add((node.id.clone(), delta)) {
+            Ok(()) => {
+              self.gossip.written(node, &self.crdt)?;
+              MsgBody {
+                typ: "add_ok".to_owned(),
+                ..Default::default()
+              }
             }
-          });
+            Err(e) => e.into(),
+          },
+          // it only grows
+          None => MsgBody::error(ErrorCode::MalformedRequest, "delta must be a u64"),
+        };
 
-          let r = MsgBody {
-            typ: "init_ok".to_owned(),
-            msg_id: gen_id(),
-            ..Default::default()
-          };
-          reply(&msg, r)?;
-        }
-        "add" => {
-          let delta = msg.body.delta.clone().unwrap().as_u64().unwrap();
-          {
-            let mut set_writer = crdt.write().unwrap();
-            set_writer.add((node_id.clone(), delta));
-          }
-
-          let r = MsgBody {
-            typ: "add_ok".to_owned(),
-            msg_id: gen_id(),
-            ..Default::default()
-          };
-          reply(&msg, r)?;
-        }
-        "replicate" => {
-          let other = GCounter::from_msg_body(&msg.body);
-
-          let mut set_writer = crdt.write().unwrap();
-          set_writer.merge(&other);
-        }
-        "read" => {
-          let r = MsgBody {
+        node.reply(&msg, r)
+      }
+      "replicate" => self.gossip.replicated(node, &msg, &mut self.crdt),
+      "read" => {
+        let r = match self.crdt.read() {
+          Ok(value) => MsgBody {
             typ: "read_ok".to_owned(),
-            msg_id: gen_id(),
-            value: Some(serde_json::Number::from(crdt.read().unwrap().read()).into()),
+            value: Some(serde_json::Number::from(value).into()),
             ..Default::default()
-          };
+          },
+          Err(e) => e.into(),
+        };
 
-          reply(&msg, r)?;
-        }
-        _ => unimplemented!("unexpected message"),
+        node.reply(&msg, r)
       }
+      _ => unimplemented!("unexpected message"),
     }
   }
 }
 
-fn reply(origin: &Message, mut resp_body: MsgBody) -> Result<()> {
-  resp_body.in_reply_to = Some(origin.body.msg_id.unwrap());
-
-  let reply = Message {
-    src: origin.dest.clone(),
-    dest: origin.src.clone(),
-    body: resp_body,
-  };
-
-  println!("{}", serde_json::to_string(&reply)?);
-
-  Ok(())
+fn main() -> Result<()> {
+  Node::run(GCounterNode {
+    crdt: GCounter::init(),
+    gossip: GossipConfig::from_env()?,
+  })
 }
@@ -1,33 +1,26 @@
 use anyhow::Result;
-use std::{
-  collections::HashSet,
-  io::{self, Write},
-  sync::{
-    atomic::{AtomicU64, Ordering},
-    Arc, RwLock,
-  },
-  thread,
-  time::Duration,
-};
+use serde_json::Value as Jval;
+use std::collections::HashSet;
 
 use maelstrom::*;
 
-struct GSet(HashSet<u64>);
+struct GSet(HashSet<CanonicalValue>);
 
 impl CRDT for GSet {
-  type Element = u64;
-  type Value = HashSet<u64>;
+  type Element = CanonicalValue;
+  type Value = HashSet<CanonicalValue>;
 
   fn init() -> Self {
     GSet(HashSet::new())
   }
 
-  fn add(&mut self, val: Self::Element) {
+  fn add(&mut self, val: Self::Element) -> Result<(), Error> {
     self.0.insert(val);
+    Ok(())
   }
 
-  fn read(&self) -> Self::Value {
-    self.0.clone()
+  fn read(&self) -> Result<Self::Value, Error> {
+    Ok(self.0.clone())
   }
 
   fn merge(&mut self, other: &Self) {
@@ -38,7 +31,7 @@ impl CRDT for GSet {
     GSet(mb.set.clone().unwrap())
   }
 
-  fn into_msg_body(&self) -> MsgBody {
+  fn to_msg_body(&self) -> MsgBody {
     MsgBody {
       set: Some(self.0.clone()),
       ..Default::default()
@@ -46,114 +39,142 @@ impl CRDT for GSet {
   }
 }
 
-fn main() -> Result<()> {
-  // logging
-  let stderr = io::stderr();
-  let mut log = stderr.lock();
-
-  // input
-  let stdin = io::stdin();
-
-  // msg_id generation
-  let msg_id = AtomicU64::new(0);
-  let gen_id = move || Some(msg_id.fetch_add(1, Ordering::SeqCst));
-
-  // node data
-  let gset = Arc::new(RwLock::new(GSet::init()));
-
-  loop {
-    let mut input = String::new();
-    if let Ok(_) = stdin.read_line(&mut input) {
-      // log.write_all(format!("Raw message: {}\n", input.trim()).as_bytes())?;
-      let msg: Message = serde_json::from_str(&input)?;
-      // log.write_all(format!("Parse result: {:?}\n", &msg).as_bytes())?;
-
-      match msg.body.typ.as_str() {
-        "init" => {
-          let node_id = msg.body.node_id.clone().unwrap();
-          let other_nodes: Vec<String> = msg
-            .body
-            .node_ids
-            .clone()
-            .unwrap()
-            .into_iter()
-            .filter(|n| n != &node_id)
-            .collect();
-
-          log.write_all(format!("Node {} initialized\n", &node_id).as_bytes())?;
-
-          // replicate thread
-          let set_reader = gset.clone();
-          thread::spawn(move || loop {
-            thread::sleep(Duration::from_millis(2_000));
-
-            let mut bd = set_reader.read().unwrap().into_msg_body();
-            bd.typ = "replicate".to_owned();
-
-            for dest in other_nodes.iter().cloned() {
-              let msg = Message {
-                src: node_id.clone(),
-                dest,
-                body: bd.clone(),
-              };
-
-              println!("{}", serde_json::to_string(&msg).unwrap());
-            }
-          });
-
-          let r = MsgBody {
-            typ: "init_ok".to_owned(),
-            msg_id: gen_id(),
+impl GSet {
+  fn digest(&self) -> Digest {
+    Digest::of(&self.0)
+  }
+
+  fn in_buckets(&self, buckets: &[usize]) -> HashSet<CanonicalValue> {
+    self
+      .0
+      .iter()
+      .filter(|e| buckets.contains(&bucket_of(*e)))
+      .cloned()
+      .collect()
+  }
+}
+
+struct GSetNode {
+  gset: GSet,
+  // replication schedule
+  gossip: GossipConfig,
+}
+
+impl Handler for GSetNode {
+  fn init(&mut self, node: &mut Node<Self>) -> Result<()> {
+    // anti-entropy, only sends the digest summary
+    // elements are transferred when peers find they differ, the mode decides which way they flow
+    node.set_interval(self.gossip.interval, |s, node| {
+      let bd = MsgBody {
+        typ: "sync".to_owned(),
+        digest: Some(s.gset.digest().summary()),
+        mode: Some(s.gossip.mode),
+        ..Default::default()
+      };
+
+      for dest in s.gossip.targets(&node.live_nodes()) {
+        node.send(&dest, bd.clone())?;
+      }
+      Ok(())
+    });
+
+    Ok(())
+  }
+
+  fn handle(&mut self, node: &mut Node<Self>, msg: Message) -> Result<()> {
+    match msg.body.typ.as_str() {
+      "add" => {
+        let elem = CanonicalValue::from(msg.body.element.clone().unwrap());
+        self.gset.add(elem.clone())?;
+
+        if self.gossip.push_on_write {
+          let bd = MsgBody {
+            typ: "replicate".to_owned(),
+            set: Some(std::iter::once(elem).collect()),
             ..Default::default()
           };
-          reply(&msg, r)?;
-        }
-        "add" => {
-          let elem = msg.body.element.clone().unwrap().as_u64().unwrap();
-          {
-            let mut set_writer = gset.write().unwrap();
-            set_writer.add(elem);
+          for dest in self.gossip.targets(&node.live_nodes()) {
+            node.send(&dest, bd.clone())?;
           }
+        }
 
-          let r = MsgBody {
-            typ: "add_ok".to_owned(),
-            msg_id: gen_id(),
+        let r = MsgBody {
+          typ: "add_ok".to_owned(),
+          ..Default::default()
+        };
+        node.reply(&msg, r)
+      }
+      "sync" => {
+        let theirs = msg.body.digest.clone().unwrap();
+        let ours = self.gset.digest();
+        if !ours.matches(&theirs) {
+          let bd = MsgBody {
+            typ: "sync_buckets".to_owned(),
+            digest: Some(ours),
+            mode: msg.body.mode,
             ..Default::default()
           };
-          reply(&msg, r)?;
-        }
-        "replicate" => {
-          let other = GSet::from_msg_body(&msg.body);
-
-          let mut set_writer = gset.write().unwrap();
-          set_writer.merge(&other);
+          node.send(&msg.src, bd)?;
         }
-        "read" => {
-          let r = MsgBody {
-            typ: "read_ok".to_owned(),
-            msg_id: gen_id(),
-            value: Some(gset.read().unwrap().read().clone().into_iter().collect()),
+        Ok(())
+      }
+      "sync_buckets" => {
+        let theirs = msg.body.digest.clone().unwrap();
+        let mode = msg.body.mode.unwrap_or(GossipMode::PushPull);
+        let buckets = self.gset.digest().diff(&theirs);
+        // when only pulling, an empty set makes them send back the whole buckets
+        let set = if mode.pushes() {
+          self.gset.in_buckets(&buckets)
+        } else {
+          HashSet::new()
+        };
+        let bd = MsgBody {
+          typ: "sync_elements".to_owned(),
+          set: Some(set),
+          buckets: Some(buckets),
+          mode: Some(mode),
+          ..Default::default()
+        };
+        node.send(&msg.src, bd)
+      }
+      "sync_elements" => {
+        let buckets = msg.body.buckets.clone().unwrap();
+        let other = GSet::from_msg_body(&msg.body);
+
+        // we now know exactly what they have in those buckets, send back only what they miss
+        let missing: HashSet<CanonicalValue> = self.gset.in_buckets(&buckets).difference(&other.0).cloned().collect();
+        self.gset.merge(&other);
+
+        if !missing.is_empty() && msg.body.mode.is_none_or(GossipMode::pulls) {
+          let bd = MsgBody {
+            typ: "replicate".to_owned(),
+            set: Some(missing),
             ..Default::default()
           };
-
-          reply(&msg, r)?;
+          node.send(&msg.src, bd)?;
         }
-        _ => unimplemented!("unexpected message"),
+        Ok(())
       }
+      "replicate" => {
+        self.gset.merge(&GSet::from_msg_body(&msg.body));
+        Ok(())
+      }
+      "read" => {
+        let r = MsgBody {
+          typ: "read_ok".to_owned(),
+          value: Some(self.gset.read()?.into_iter().map(Jval::from).collect()),
+          ..Default::default()
+        };
+        node.reply(&msg, r)
+      }
+      _ => unimplemented!("unexpected message"),
     }
   }
 }
 
-fn reply(origin: &Message, mut resp_body: MsgBody) -> Result<()> {
-  resp_body.in_reply_to = Some(origin.body.msg_id.unwrap());
-
-  let reply = Message {
-    src: origin.dest.clone(),
-    dest: origin.src.clone(),
-    body: resp_body,
-  };
-
-  println!("{}", serde_json::to_string(&reply)?);
-
-  Ok(())
+fn main() -> Result<()> {
+  Node::run(GSetNode {
+    gset: GSet::init(),
+    gossip: GossipConfig::from_env()?,
+  })
 }
@@ -1,65 +1,58 @@
 use anyhow::Result;
-use std::{
-  collections::HashMap,
-  io::{self, Write},
-  sync::{
-    atomic::{AtomicU64, Ordering},
-    Arc, RwLock,
-  },
-  thread,
-  time::Duration,
-};
+use serde_json::Value as Jval;
+use std::collections::HashMap;
 
 use maelstrom::*;
 
 #[derive(Debug, Clone, Default)]
 struct PNCounter {
-  inc: HashMap<String, u64>,
-  dec: HashMap<String, u64>,
+  inc: HashMap<String, Count>,
+  dec: HashMap<String, Count>,
+}
+
+impl PNCounter {
+  // the counters `delta` goes to
+  fn side(&mut self, delta: &Delta) -> &mut HashMap<String, Count> {
+    if is_positive(delta) {
+      &mut self.inc
+    } else {
+      &mut self.dec
+    }
+  }
 }
 
 impl CRDT for PNCounter {
-  type Element = (String, i64);
-  type Value = i64;
+  type Element = (String, Delta);
+  type Value = Jval;
 
   fn init() -> Self {
     Default::default()
   }
 
-  fn add(&mut self, (node, delta): Self::Element) {
-    let m = if delta.is_positive() {
-      &mut self.inc
-    } else {
-      &mut self.dec
-    };
-
-    if let Some(val) = m.get_mut(&node) {
-      *val += delta.abs() as u64;
-    } else {
-      m.insert(node, delta.abs() as u64);
+  fn add(&mut self, (node, delta): Self::Element) -> Result<(), Error> {
+    // the total has to fit as well, or reads fail until it's back in range
+    // merges can still take it out of range, adds that bring it back are fine
+    let fits = self.read().is_ok();
+    let prev = self.side(&delta).get(&node).cloned().unwrap_or_default();
+    let next = incr(&prev, &delta)?;
+    self.side(&delta).insert(node.clone(), next);
+
+    match self.read() {
+      Err(e) if fits => {
+        self.side(&delta).insert(node, prev);
+        Err(e)
+      }
+      _ => Ok(()),
     }
   }
 
-  fn read(&self) -> Self::Value {
-    (self.inc.values().sum::<u64>() as i64) - (self.dec.values().sum::<u64>() as i64)
+  fn read(&self) -> Result<Self::Value, Error> {
+    total(&self.inc, &self.dec)
   }
 
   fn merge(&mut self, other: &Self) {
-    for (ok, ov) in other.inc.iter() {
-      if let Some(val) = self.inc.get_mut(ok) {
-        *val = (*val).max(*ov);
-      } else {
-        self.inc.insert(ok.clone(), ov.clone());
-      }
-    }
-
-    for (ok, ov) in other.dec.iter() {
-      if let Some(val) = self.dec.get_mut(ok) {
-        *val = (*val).max(*ov);
-      } else {
-        self.dec.insert(ok.clone(), ov.clone());
-      }
-    }
+    merge_max(&mut self.inc, &other.inc);
+    merge_max(&mut self.dec, &other.dec);
   }
 
   fn from_msg_body(mb: &MsgBody) -> Self {
@@ -67,7 +60,7 @@ impl CRDT for PNCounter {
     PNCounter { inc, dec }
   }
 
-  fn into_msg_body(&self) -> MsgBody {
+  fn to_msg_body(&self) -> MsgBody {
     MsgBody {
       pn_counters: Some((self.inc.clone(), self.dec.clone())),
       ..Default::default()
@@ -75,116 +68,127 @@ impl CRDT for PNCounter {
   }
 }
 
-fn main() -> Result<()> {
-  // logging
-  let stderr = io::stderr();
-  let mut log = stderr.lock();
-
-  // input
-  let stdin = io::stdin();
-
-  // msg_id generation
-  let msg_id = AtomicU64::new(0);
-  let gen_id = move || Some(msg_id.fetch_add(1, Ordering::SeqCst));
-
-  // node data
-  let mut node_id = String::new();
-  let crdt = Arc::new(RwLock::new(PNCounter::init()));
-
-  loop {
-    let mut input = String::new();
-    if let Ok(_) = stdin.read_line(&mut input) {
-      // log.write_all(format!("Raw message: {}\n", input.trim()).as_bytes())?;
-      let msg: Message = serde_json::from_str(&input)?;
-      // log.write_all(format!("Parse result: {:?}\n", &msg).as_bytes())?;
-
-      match msg.body.typ.as_str() {
-        "init" => {
-          node_id = msg.body.node_id.clone().unwrap();
-          let other_nodes: Vec<String> = msg
-            .body
-            .node_ids
-            .clone()
-            .unwrap()
-            .into_iter()
-            .filter(|n| n != &node_id)
-            .collect();
-
-          log.write_all(format!("Node {} initialized\n", &node_id).as_bytes())?;
-
-          // replicate thread
-          let reader = crdt.clone();
-          let ni = node_id.clone();
-          thread::spawn(move || loop {
-            thread::sleep(Duration::from_millis(2_000));
-
-            let mut bd = reader.read().unwrap().into_msg_body();
-            bd.typ = "replicate".to_owned();
-
-            for dest in other_nodes.iter().cloned() {
-              let msg = Message {
-                src: ni.clone(),
-                dest,
-                body: bd.clone(),
-              };
-
-              println!("{}", serde_json::to_string(&msg).unwrap());
+// what `add` takes, an i64, or any integer with the `bigint` feature
+#[cfg(not(feature = "bigint"))]
+type Delta = i64;
+#[cfg(feature = "bigint")]
+type Delta = num_bigint::BigInt;
+
+#[cfg(not(feature = "bigint"))]
+const BAD_DELTA: &str = "delta must be an i64";
+#[cfg(feature = "bigint")]
+const BAD_DELTA: &str = "delta must be an integer";
+
+#[cfg(not(feature = "bigint"))]
+fn parse_delta(v: &Jval) -> Option<Delta> {
+  v.as_i64()
+}
+
+#[cfg(not(feature = "bigint"))]
+fn is_positive(delta: &Delta) -> bool {
+  delta.is_positive()
+}
+
+#[cfg(not(feature = "bigint"))]
+fn incr(val: &Count, delta: &Delta) -> Result<Count, Error> {
+  val
+    .checked_add(delta.unsigned_abs())
+    .ok_or_else(|| Error::new(ErrorCode::Abort, "counter overflow"))
+}
+
+#[cfg(not(feature = "bigint"))]
+fn total(inc: &HashMap<String, Count>, dec: &HashMap<String, Count>) -> Result<Jval, Error> {
+  use std::convert::TryFrom;
+
+  // a sum of u64 can't overflow i128 unless there are 2^63 nodes
+  let sum = |m: &HashMap<String, Count>| m.values().map(|v| i128::from(*v)).sum::<i128>();
+  let value =
+    i64::try_from(sum(inc) - sum(dec)).map_err(|_| Error::new(ErrorCode::Abort, "counter value out of i64 range"))?;
+  Ok(serde_json::Number::from(value).into())
+}
+
+#[cfg(feature = "bigint")]
+fn parse_delta(v: &Jval) -> Option<Delta> {
+  // arbitrary_precision keeps the number as written, fractions don't parse
+  match v {
+    Jval::Number(n) => n.to_string().parse().ok(),
+    _ => None,
+  }
+}
+
+#[cfg(feature = "bigint")]
+fn is_positive(delta: &Delta) -> bool {
+  delta.sign() == num_bigint::Sign::Plus
+}
+
+#[cfg(feature = "bigint")]
+fn incr(val: &Count, delta: &Delta) -> Result<Count, Error> {
+  Ok(val + delta.magnitude())
+}
+
+#[cfg(feature = "bigint")]
+fn total(inc: &HashMap<String, Count>, dec: &HashMap<String, Count>) -> Result<Jval, Error> {
+  use num_bigint::BigInt;
+
+  let sum = |m: &HashMap<String, Count>| m.values().map(|v| BigInt::from(v.clone())).sum::<BigInt>();
+  // arbitrary_precision keeps all the digits when parsed as a JSON number
+  serde_json::from_str(&(sum(inc) - sum(dec)).to_string())
+    .map_err(|e| Error::new(ErrorCode::Crash, format!("can't encode counter value: {}", e)))
+}
+
+struct PNCounterNode {
+  crdt: PNCounter,
+  // replication schedule
+  gossip: GossipConfig,
+}
+
+impl Handler for PNCounterNode {
+  fn init(&mut self, node: &mut Node<Self>) -> Result<()> {
+    node.set_interval(self.gossip.interval, |s, node| s.gossip.gossip(node, &s.crdt));
+
+    Ok(())
+  }
+
+  fn handle(&mut self, node: &mut Node<Self>, msg: Message) -> Result<()> {
+    match msg.body.typ.as_str() {
+      "add" => {
+        let r = match msg.body.delta.as_ref().and_then(parse_delta) {
+          Some(delta) => match self.crdt.add((node.id.clone(), delta)) {
+            Ok(()) => {
+              self.gossip.written(node, &self.crdt)?;
+              MsgBody {
+                typ: "add_ok".to_owned(),
+                ..Default::default()
+              }
             }
-          });
+            Err(e) => e.into(),
+          },
+          None => MsgBody::error(ErrorCode::MalformedRequest, BAD_DELTA),
+        };
 
-          let r = MsgBody {
-            typ: "init_ok".to_owned(),
-            msg_id: gen_id(),
-            ..Default::default()
-          };
-          reply(&msg, r)?;
-        }
-        "add" => {
-          let delta = msg.body.delta.clone().unwrap().as_i64().unwrap();
-          {
-            let mut set_writer = crdt.write().unwrap();
-            set_writer.add((node_id.clone(), delta));
-          }
-
-          let r = MsgBody {
-            typ: "add_ok".to_owned(),
-            msg_id: gen_id(),
-            ..Default::default()
-          };
-          reply(&msg, r)?;
-        }
-        "replicate" => {
-          let other = PNCounter::from_msg_body(&msg.body);
-
-          let mut writer = crdt.write().unwrap();
-          writer.merge(&other);
-        }
-        "read" => {
-          let r = MsgBody {
+        node.reply(&msg, r)
+      }
+      "replicate" => self.gossip.replicated(node, &msg, &mut self.crdt),
+      "read" => {
+        let r = match self.crdt.read() {
+          Ok(value) => MsgBody {
             typ: "read_ok".to_owned(),
-            msg_id: gen_id(),
-            value: Some(serde_json::Number::from(crdt.read().unwrap().read()).into()),
+            value: Some(value),
             ..Default::default()
-          };
+          },
+          Err(e) => e.into(),
+        };
 
-          reply(&msg, r)?;
-        }
-        _ => unimplemented!("unexpected message"),
+        node.reply(&msg, r)
       }
+      _ => unimplemented!("unexpected message"),
     }
   }
 }
 
-fn reply(origin: &Message, mut resp_body: MsgBody) -> Result<()> {
-  resp_body.in_reply_to = Some(origin.body.msg_id.unwrap());
-
-  let reply = Message {
-    src: origin.dest.clone(),
-    dest: origin.src.clone(),
-    body: resp_body,
-  };
-
-  println!("{}", serde_json::to_string(&reply)?);
-
-  Ok(())
+fn main() -> Result<()> {
+  Node::run(PNCounterNode {
+    crdt: PNCounter::init(),
+    gossip: GossipConfig::from_env()?,
+  })
 }
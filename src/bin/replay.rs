@@ -0,0 +1,128 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::Value as Jval;
+use std::{
+  collections::HashMap,
+  env,
+  io::{BufRead, BufReader, Write},
+  process::{Command, Stdio},
+  thread,
+  time::{Duration, Instant},
+};
+
+use maelstrom::*;
+
+// covers the replayed node starting later than the recorded one did
+const SLACK: Duration = Duration::from_millis(100);
+
+// feeds the inbound messages of a trace recorded with `--record` to a workload
+// binary and diffs what it sends against the recording
+// inputs keep their recorded timing so timers fire about as they did, unless `--fast`
+// order isn't compared, only which messages were sent
+fn main() -> Result<()> {
+  let usage = || anyhow!("usage: replay [--fast] <trace.jsonl> <binary> [args..]");
+  let mut args = env::args().skip(1).peekable();
+  let fast = args.next_if_eq("--fast").is_some();
+  let trace = args.next().ok_or_else(usage)?;
+  let bin = args.next().ok_or_else(usage)?;
+
+  let records = read_trace(&trace)?;
+  let expected: Vec<String> = records
+    .iter()
+    .filter(|r| r.dir == Direction::Out)
+    .map(|r| canonical(&serde_json::to_string(&r.msg)?))
+    .collect::<Result<_>>()?;
+
+  let mut child = Command::new(&bin)
+    .args(args)
+    // don't overwrite the trace we're replaying
+    .env_remove("MAELSTROM_RECORD")
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .spawn()
+    .with_context(|| format!("starting {}", bin))?;
+
+  let stdout = child.stdout.take().unwrap();
+  let reader = thread::spawn(move || BufReader::new(stdout).lines().map_while(Result::ok).collect::<Vec<_>>());
+
+  let started = Instant::now();
+  let wait_until = |t: f64| {
+    if !fast {
+      thread::sleep((started + Duration::from_secs_f64(t)).saturating_duration_since(Instant::now()));
+    }
+  };
+
+  let mut stdin = child.stdin.take().unwrap();
+  for r in records.iter().filter(|r| r.dir == Direction::In) {
+    wait_until(r.t);
+    if writeln!(stdin, "{}", serde_json::to_string(&r.msg)?).is_err() {
+      eprintln!("{} stopped reading its input", bin);
+      break;
+    }
+  }
+  // give timers the rest of the recording to fire
+  if let Some(last) = records.last() {
+    wait_until(last.t + SLACK.as_secs_f64());
+  }
+  drop(stdin);
+
+  let status = child.wait()?;
+  let actual: Vec<String> = reader
+    .join()
+    .map_err(|_| anyhow!("reading {} output failed", bin))?
+    .iter()
+    .map(|l| canonical(l))
+    .collect::<Result<_>>()?;
+
+  let diffs = diff(&expected, &actual);
+  println!(
+    "{} recorded, {} replayed, {} differences",
+    expected.len(),
+    actual.len(),
+    diffs
+  );
+
+  if !status.success() {
+    bail!("{} exited with {}", bin, status);
+  }
+  if diffs > 0 {
+    std::process::exit(1);
+  }
+  Ok(())
+}
+
+// object keys come out sorted, so equal messages compare equal as strings
+fn canonical(line: &str) -> Result<String> {
+  let v: Jval = serde_json::from_str(line).with_context(|| format!("parsing {}", line))?;
+  Ok(v.to_string())
+}
+
+// prints recorded messages that weren't sent as `-`, and sent ones that weren't recorded as `+`
+fn diff(expected: &[String], actual: &[String]) -> usize {
+  let mut counts: HashMap<&str, i64> = HashMap::new();
+  for x in expected {
+    *counts.entry(x).or_default() += 1;
+  }
+  for x in actual {
+    *counts.entry(x).or_default() -= 1;
+  }
+
+  let mut diffs = 0;
+  for x in expected {
+    let c = counts.get_mut(x.as_str()).unwrap();
+    if *c > 0 {
+      *c -= 1;
+      diffs += 1;
+      println!("- {}", x);
+    }
+  }
+  for x in actual {
+    let c = counts.get_mut(x.as_str()).unwrap();
+    if *c < 0 {
+      *c += 1;
+      diffs += 1;
+      println!("+ {}", x);
+    }
+  }
+
+  diffs
+}
@@ -1,13 +1,7 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use serde_json::{Number as Jnum, Value as Jval};
-use std::{
-  collections::HashMap,
-  io::{self, BufRead, BufReader, Stdin, Write},
-  sync::atomic::{AtomicU64, Ordering},
-  time::Duration,
-};
-use timeout_readwrite::TimeoutReader;
+use std::collections::HashMap;
 
 use maelstrom::*;
 
@@ -50,8 +44,7 @@ impl Database {
   fn commit(&mut self, txn: &[Op]) -> Vec<Vec<Jval>> {
     txn
       .iter()
-      .cloned()
-      .map(|op| match op {
+      .map(|op| match *op {
         Op::Append(k, v) => {
           if let Some(list) = self.0.get_mut(&k) {
             list.push(v);
@@ -97,144 +90,81 @@ impl From<Database> for Jval {
   }
 }
 
-fn main() -> Result<()> {
-  // logging
-  let stderr = io::stderr();
-  let mut log = stderr.lock();
-
-  // input
-  let stdin = io::stdin();
-  let mut stdin = BufReader::new(TimeoutReader::new(stdin, Duration::from_millis(500)));
-
-  // msg_id generation
-  let msg_id = AtomicU64::new(0);
-  let gen_id = move || Some(msg_id.fetch_add(1, Ordering::SeqCst));
-
-  // node data
-  let mut node_id = String::new();
-
-  loop {
-    let mut input = String::new();
-    if let Ok(_) = stdin.read_line(&mut input) {
-      // log.write_all(format!("Raw message: {}\n", input.trim()).as_bytes())?;
-      let msg: Message = serde_json::from_str(&input)?;
-      // log.write_all(format!("Parse result: {:?}\n", &msg).as_bytes())?;
-
-      match msg.body.typ.as_str() {
-        "init" => {
-          node_id = msg.body.node_id.clone().unwrap();
-
-          log.write_all(format!("Node {} initialized\n", &node_id).as_bytes())?;
-
-          let r = MsgBody {
-            typ: "init_ok".to_owned(),
-            msg_id: gen_id(),
-            ..Default::default()
-          };
-          reply(&msg, r)?;
-        }
-        "txn" => {
-          let db1: Database = rpc(
-            &mut stdin,
-            Message {
-              src: node_id.clone(),
-              dest: "lin-kv".to_owned(),
-              body: MsgBody {
-                typ: "read".to_owned(),
-                key: Some(DB_KEY.into()),
-                ..Default::default()
-              },
-            },
-          )
-          .unwrap()
-          .body
-          .value
-          .map_or(Default::default(), |x| {
-            let db = x
-              .as_object()
-              .unwrap()
-              .iter()
-              .map(|(k, v)| {
-                (
-                  k.parse::<u64>().unwrap(),
-                  v.as_array()
-                    .cloned()
-                    .unwrap()
-                    .into_iter()
-                    .map(|x| x.as_u64().unwrap())
-                    .collect::<Vec<u64>>(),
-                )
-              })
-              .collect();
-            Database(db)
-          });
-
-          let mut db2 = db1.clone();
-
-          let t: Vec<Op> = msg.body.txn.clone().unwrap().iter().map(|x| Op::from_txn(x)).collect();
-          let ret = db2.commit(&t);
-
-          let cas_resp = rpc(
-            &mut stdin,
-            Message {
-              src: node_id.clone(),
-              dest: "lin-kv".to_owned(),
-              body: MsgBody {
-                typ: "cas".to_owned(),
-                key: Some(DB_KEY.into()),
-                from: Some(db1.into()),
-                to: Some(db2.into()),
-                create_if_not_exists: Some(true),
-                ..Default::default()
-              },
-            },
-          );
-
-          if cas_resp.map_or(false, |cr| cr.body.typ == "cas_ok") {
-            let r = MsgBody {
-              typ: "txn_ok".to_owned(),
-              msg_id: gen_id(),
-              txn: Some(ret),
-              ..Default::default()
-            };
-            reply(&msg, r)?;
-          } else {
-            let r = MsgBody {
-              typ: "error".to_owned(),
-              msg_id: gen_id(),
-              code: Some(30),
-              text: Some("CAS failed".to_owned()),
-              ..Default::default()
-            };
-            reply(&msg, r)?;
-          }
-        }
-        _ => unimplemented!("unexpected message"),
+impl From<&Jval> for Database {
+  fn from(x: &Jval) -> Self {
+    let db = x
+      .as_object()
+      .unwrap()
+      .iter()
+      .map(|(k, v)| {
+        (
+          k.parse::<u64>().unwrap(),
+          v.as_array()
+            .cloned()
+            .unwrap()
+            .into_iter()
+            .map(|x| x.as_u64().unwrap())
+            .collect::<Vec<u64>>(),
+        )
+      })
+      .collect();
+    Database(db)
+  }
+}
+
+// a txn waiting on lin-kv doesn't hold up the others
+struct TxnListAppend;
+
+impl TxnListAppend {
+  async fn transact(&self, ctx: &AsyncContext, msg: &Message) -> Result<MsgBody> {
+    let kv = Kv::lin(ctx);
+
+    // the key doesn't exist before the first commit
+    let db1: Database = kv
+      .read(DB_KEY)
+      .await?
+      .as_ref()
+      .map_or(Default::default(), Database::from);
+
+    let mut db2 = db1.clone();
+
+    let t: Vec<Op> = msg.body.txn.clone().unwrap().iter().map(|x| Op::from_txn(x)).collect();
+    let ret = db2.commit(&t);
+
+    match kv.cas(DB_KEY, db1, db2, true).await {
+      Ok(()) => Ok(MsgBody {
+        typ: "txn_ok".to_owned(),
+        txn: Some(ret),
+        ..Default::default()
+      }),
+      Err(e)
+        if e
+          .downcast_ref::<Error>()
+          .is_some_and(|e| e.code == ErrorCode::PreconditionFailed) =>
+      {
+        Ok(MsgBody::error(ErrorCode::TxnConflict, "CAS failed"))
       }
+      Err(e) => Err(e),
     }
   }
 }
 
-fn reply(origin: &Message, mut resp_body: MsgBody) -> Result<()> {
-  resp_body.in_reply_to = Some(origin.body.msg_id.unwrap());
-
-  let reply = Message {
-    src: origin.dest.clone(),
-    dest: origin.src.clone(),
-    body: resp_body,
-  };
-
-  println!("{}", serde_json::to_string(&reply)?);
-
-  Ok(())
+impl AsyncHandler for TxnListAppend {
+  async fn handle(&self, ctx: &AsyncContext, msg: Message) -> Result<()> {
+    match msg.body.typ.as_str() {
+      "txn" => {
+        let r = match self.transact(ctx, &msg).await {
+          Ok(r) => r,
+          // e.g. lin-kv timing out
+          Err(e) => Error::from_any(e).into(),
+        };
+        ctx.reply(&msg, r)
+      }
+      _ => unimplemented!("unexpected message"),
+    }
+  }
 }
 
-fn rpc(stdin: &mut BufReader<TimeoutReader<Stdin>>, req: Message) -> Result<Message> {
-  println!("{}", serde_json::to_string(&req)?);
-
-  let mut input = String::new();
-  let _ = stdin.read_line(&mut input)?;
-
-  let msg: Message = serde_json::from_str(&input)?;
-  Ok(msg)
+fn main() -> Result<()> {
+  Node::run(Async::from_config(TxnListAppend)?)
 }
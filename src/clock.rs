@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+use std::{
+  cmp::Ordering,
+  collections::HashMap,
+  hash::Hash,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::NodeID;
+
+// orders events by causality across nodes, in one number
+// ticks on every local event, and jumps past every time it sees
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct LamportClock(u64);
+
+impl LamportClock {
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  pub fn time(&self) -> u64 {
+    self.0
+  }
+
+  // a local event, or a send, returns its time
+  pub fn tick(&mut self) -> u64 {
+    self.0 += 1;
+    self.0
+  }
+
+  // a receive of something stamped `time`, returns the time of the receive
+  pub fn observe(&mut self, time: u64) -> u64 {
+    self.0 = self.0.max(time) + 1;
+    self.0
+  }
+}
+
+// one counter per node, tells apart events that are causally ordered from
+// concurrent ones
+// nodes without an entry are at 0
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VectorClock(HashMap<NodeID, u64>);
+
+impl VectorClock {
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  pub fn get(&self, node: &str) -> u64 {
+    self.0.get(node).copied().unwrap_or_default()
+  }
+
+  // an event on `node`, returns its counter
+  pub fn tick(&mut self, node: &str) -> u64 {
+    let c = self.0.entry(node.to_owned()).or_default();
+    *c += 1;
+    *c
+  }
+
+  // takes in everything `other` has seen
+  pub fn merge(&mut self, other: &VectorClock) {
+    merge_max(&mut self.0, &other.0);
+  }
+
+  // neither happened before the other
+  pub fn concurrent(&self, other: &VectorClock) -> bool {
+    self.partial_cmp(other).is_none()
+  }
+}
+
+impl PartialEq for VectorClock {
+  fn eq(&self, other: &Self) -> bool {
+    self.partial_cmp(other) == Some(Ordering::Equal)
+  }
+}
+
+impl Eq for VectorClock {}
+
+// `Less` is happened before, `None` is concurrent
+impl PartialOrd for VectorClock {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    compare_entries(&self.0, &other.0)
+  }
+}
+
+// for vector shaped state, e.g. counters per node: keeps the larger of each entry
+pub fn merge_max<K: Hash + Eq + Clone, C: Ord + Clone>(into: &mut HashMap<K, C>, other: &HashMap<K, C>) {
+  for (k, v) in other {
+    match into.get_mut(k) {
+      Some(ours) if *ours < *v => *ours = v.clone(),
+      Some(_) => {}
+      None => {
+        into.insert(k.clone(), v.clone());
+      }
+    }
+  }
+}
+
+// entry by entry, missing ones are zero: `Less` if all of `a` are at most
+// those of `b`, `None` if some are less and some greater
+pub fn compare_entries<K: Hash + Eq, C: Ord + Default>(a: &HashMap<K, C>, b: &HashMap<K, C>) -> Option<Ordering> {
+  let zero = C::default();
+  let (mut less, mut greater) = (false, false);
+  for (k, x) in a {
+    match x.cmp(b.get(k).unwrap_or(&zero)) {
+      Ordering::Less => less = true,
+      Ordering::Greater => greater = true,
+      Ordering::Equal => {}
+    }
+  }
+  for (_, y) in b.iter().filter(|(k, _)| !a.contains_key(*k)) {
+    match zero.cmp(y) {
+      Ordering::Less => less = true,
+      Ordering::Greater => greater = true,
+      Ordering::Equal => {}
+    }
+  }
+
+  match (less, greater) {
+    (false, false) => Some(Ordering::Equal),
+    (true, false) => Some(Ordering::Less),
+    (false, true) => Some(Ordering::Greater),
+    (true, true) => None,
+  }
+}
+
+// a hybrid logical clock reading, Kulkarni et al.
+// ordered by wall time, then by the logical counter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct HlcTimestamp {
+  // ms since the epoch
+  pub wall: u64,
+  // events within the same wall time
+  pub logical: u64,
+}
+
+// timestamps close to physical time that still respect causality, like
+// Lamport's, however far apart the nodes' clocks are
+#[derive(Debug, Clone, Default)]
+pub struct HybridClock {
+  last: HlcTimestamp,
+}
+
+impl HybridClock {
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  // a local event, or a send
+  pub fn now(&mut self) -> HlcTimestamp {
+    self.now_at(physical_now())
+  }
+
+  // a receive of something stamped `ts`
+  pub fn observe(&mut self, ts: HlcTimestamp) -> HlcTimestamp {
+    self.observe_at(ts, physical_now())
+  }
+
+  // like `now`, with the physical time in ms given
+  pub fn now_at(&mut self, physical: u64) -> HlcTimestamp {
+    self.last = if physical > self.last.wall {
+      HlcTimestamp {
+        wall: physical,
+        logical: 0,
+      }
+    } else {
+      HlcTimestamp {
+        logical: self.last.logical + 1,
+        ..self.last
+      }
+    };
+    self.last
+  }
+
+  // like `observe`, with the physical time in ms given
+  pub fn observe_at(&mut self, ts: HlcTimestamp, physical: u64) -> HlcTimestamp {
+    let wall = physical.max(self.last.wall).max(ts.wall);
+    let logical = match (wall == self.last.wall, wall == ts.wall) {
+      (true, true) => self.last.logical.max(ts.logical) + 1,
+      (true, false) => self.last.logical + 1,
+      (false, true) => ts.logical + 1,
+      (false, false) => 0,
+    };
+
+    self.last = HlcTimestamp { wall, logical };
+    self.last
+  }
+}
+
+fn physical_now() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |d| d.as_millis() as u64)
+}
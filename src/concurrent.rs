@@ -0,0 +1,221 @@
+use anyhow::{anyhow, Result};
+use std::{
+  collections::HashMap,
+  str::FromStr,
+  sync::{mpsc, Arc, Mutex},
+  thread,
+  time::{Duration, Instant},
+};
+
+use super::{
+  config, error,
+  node::{drop_reply, forwarded, others},
+  quorum::fan_out,
+  Error, ErrorCode, Handler, Message, MsgBody, Node, NodeID, Outbox, Tally, WorkerPool,
+};
+
+// which messages a `Concurrent` handler runs one at a time, in arrival order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderBy {
+  // none, any two messages may be handled at once
+  Unordered,
+  // messages from the same node or client
+  Source,
+  // messages with the same `SyncHandler::key`
+  Key,
+}
+
+impl FromStr for OrderBy {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "none" => Ok(OrderBy::Unordered),
+      "source" => Ok(OrderBy::Source),
+      "key" => Ok(OrderBy::Key),
+      _ => Err(anyhow!("expected none, source or key")),
+    }
+  }
+}
+
+// a workload that handles many messages at once, on a worker pool
+// unlike `Handler` the state is shared between threads, behind its own locks,
+// and handlers can block, e.g. waiting on `Context::rpc`
+pub trait SyncHandler: Send + Sync + Sized + 'static {
+  fn init(&self, _ctx: &Context) -> Result<()> {
+    Ok(())
+  }
+
+  fn handle(&self, ctx: &Context, msg: Message) -> Result<()>;
+
+  // what `OrderBy::Key` serializes on, the message's `key` by default
+  fn key(&self, msg: &Message) -> Option<String> {
+    msg.body.key.as_ref().map(|k| k.to_string())
+  }
+}
+
+type Waiting = Arc<Mutex<HashMap<u64, mpsc::Sender<Message>>>>;
+
+// what a `SyncHandler` gets to talk to other nodes, from any thread
+pub struct Context {
+  pub id: NodeID,
+  pub node_ids: Vec<NodeID>,
+  outbox: Outbox,
+  waiting: Waiting,
+  rpc_timeout: Duration,
+}
+
+impl Context {
+  pub fn other_nodes(&self) -> Vec<NodeID> {
+    others(&self.id, &self.node_ids)
+  }
+
+  pub fn gen_id(&self) -> u64 {
+    self.outbox.gen_id()
+  }
+
+  pub fn send(&self, dest: &str, body: MsgBody) -> Result<()> {
+    self.outbox.send_from(&self.id, dest, body)
+  }
+
+  // see `Outbox::reply`
+  pub fn reply(&self, origin: &Message, resp_body: MsgBody) -> Result<()> {
+    self.outbox.reply(origin, resp_body)
+  }
+
+  // sends `body` and blocks until the reply, a timeout is an `ErrorCode::Timeout` error
+  pub fn rpc(&self, dest: &str, mut body: MsgBody) -> Result<Message> {
+    let id = self.gen_id();
+    body.msg_id = Some(id);
+    let (tx, rx) = mpsc::channel();
+    self.waiting.lock().unwrap().insert(id, tx);
+    self.send(dest, body)?;
+
+    let reply = rx.recv_timeout(self.rpc_timeout);
+    self.waiting.lock().unwrap().remove(&id);
+    reply.map_err(|_| Error::new(ErrorCode::Timeout, format!("no reply from {}", dest)).into())
+  }
+
+  // sends `body` to each of `nodes` and blocks until `need` of them replied
+  // successfully, or that can't happen within the RPC timeout
+  // later replies are ignored
+  pub fn quorum(&self, nodes: &[NodeID], body: MsgBody, need: usize) -> Result<Vec<Message>> {
+    let (tx, rx) = mpsc::channel();
+    let ids = fan_out(&self.outbox, &self.id, nodes, &body, |id| {
+      self.waiting.lock().unwrap().insert(id, tx.clone());
+    })?;
+
+    let deadline = Instant::now() + self.rpc_timeout;
+    let mut tally = Tally::new(need, nodes.len());
+    let outcome = loop {
+      if let Some(outcome) = tally.outcome() {
+        break outcome;
+      }
+      // past the deadline, whatever is left counts as timed out
+      tally.add(rx.recv_timeout(deadline.saturating_duration_since(Instant::now())).ok());
+    };
+
+    let mut waiting = self.waiting.lock().unwrap();
+    for id in ids {
+      waiting.remove(&id);
+    }
+    outcome.map_err(Into::into)
+  }
+
+  // hands `msg` to `to` and relays its reply back to the sender as ours,
+  // no reply within the RPC timeout is answered with temporarily-unavailable
+  pub fn forward(&self, msg: &Message, to: &str) -> Result<()> {
+    forwarded(&self.outbox, msg, to, self.rpc(to, msg.body.clone()))
+  }
+}
+
+// runs a `SyncHandler` as a `Handler`, the event loop hands messages to the pool
+// and routes replies to the workers waiting on them
+// set up with `--workers` (default: one per CPU) and `--ordering` (default `source`)
+pub struct Concurrent<H> {
+  handler: Arc<H>,
+  workers: usize,
+  ordering: OrderBy,
+  rpc_timeout: Duration,
+  waiting: Waiting,
+  ctx: Option<Arc<Context>>,
+  pool: Option<WorkerPool>,
+}
+
+impl<H: SyncHandler> Concurrent<H> {
+  pub fn new(handler: H, workers: usize, ordering: OrderBy) -> Self {
+    Concurrent {
+      handler: Arc::new(handler),
+      workers,
+      ordering,
+      rpc_timeout: Duration::from_secs(1),
+      waiting: Default::default(),
+      ctx: None,
+      pool: None,
+    }
+  }
+
+  pub fn from_config(handler: H) -> Result<Self> {
+    let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut c = Concurrent::new(
+      handler,
+      config::parse_or("workers", cpus)?,
+      config::parse_or("ordering", OrderBy::Source)?,
+    );
+    c.rpc_timeout = Duration::from_millis(config::parse_or("rpc-timeout", 1_000)?);
+    Ok(c)
+  }
+}
+
+impl<H: SyncHandler> Handler for Concurrent<H> {
+  fn init(&mut self, node: &mut Node<Self>) -> Result<()> {
+    node.settles_requests = true;
+    let ctx = Arc::new(Context {
+      id: node.id.clone(),
+      node_ids: node.node_ids.clone(),
+      outbox: node.outbox(),
+      waiting: self.waiting.clone(),
+      rpc_timeout: self.rpc_timeout,
+    });
+    self.ctx = Some(ctx.clone());
+    self.pool = Some(WorkerPool::new(self.workers));
+    // let running handlers finish, while their replies can still go out
+    node.on_shutdown(|s, _| {
+      if let Some(pool) = s.pool.take() {
+        pool.join();
+      }
+      Ok(())
+    });
+
+    self.handler.init(&ctx)
+  }
+
+  fn handle(&mut self, _node: &mut Node<Self>, msg: Message) -> Result<()> {
+    let key = match self.ordering {
+      OrderBy::Unordered => None,
+      OrderBy::Source => Some(msg.src.clone()),
+      OrderBy::Key => self.handler.key(&msg),
+    };
+    let (handler, ctx) = (self.handler.clone(), self.ctx.clone().unwrap());
+    self.pool.as_ref().unwrap().submit(key, move || {
+      let (typ, src, id) = (msg.body.typ.clone(), msg.src.clone(), msg.body.msg_id);
+      if let Err(e) = handler.handle(&ctx, msg) {
+        error!("Failed handling {}: {}", typ, e);
+      }
+      ctx.outbox.settle(&src, id);
+    });
+    Ok(())
+  }
+
+  fn handle_reply(&mut self, _node: &mut Node<Self>, msg: Message) -> Result<()> {
+    let waiter = self.waiting.lock().unwrap().remove(&msg.body.in_reply_to.unwrap());
+    match waiter {
+      // the worker may have given up on it just now
+      Some(waiter) => {
+        let _ = waiter.send(msg);
+      }
+      None => drop_reply(&msg),
+    }
+    Ok(())
+  }
+}
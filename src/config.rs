@@ -0,0 +1,49 @@
+use anyhow::{anyhow, Result};
+use std::{env, fmt::Display, str::FromStr, time::Duration};
+
+// looks up a setting, first as `--name value` or `--name=value` on the command line,
+// then as the `MAELSTROM_NAME` environment variable (dashes become underscores)
+// a bare `--name`, last or followed by another flag, is the empty string
+pub fn lookup(name: &str) -> Option<String> {
+  let flag = format!("--{}", name);
+  let mut args = env::args().skip(1).peekable();
+  while let Some(arg) = args.next() {
+    if arg == flag {
+      return Some(args.next_if(|next| !next.starts_with("--")).unwrap_or_default());
+    }
+    if let Some(val) = arg.strip_prefix(&flag).and_then(|rest| rest.strip_prefix('=')) {
+      return Some(val.to_owned());
+    }
+  }
+
+  env::var(format!("MAELSTROM_{}", name.to_uppercase().replace('-', "_"))).ok()
+}
+
+pub fn parse_or<T>(name: &str, default: T) -> Result<T>
+where
+  T: FromStr,
+  T::Err: Display,
+{
+  match lookup(name) {
+    Some(val) => val.parse().map_err(|e| anyhow!("invalid {} {:?}: {}", name, val, e)),
+    None => Ok(default),
+  }
+}
+
+// a switch, `--name` alone turns it on, or it's one of 1, 0, true and false
+pub fn flag(name: &str, default: bool) -> Result<bool> {
+  match lookup(name).as_deref() {
+    None => Ok(default),
+    Some("" | "1" | "true") => Ok(true),
+    Some("0" | "false") => Ok(false),
+    Some(val) => Err(anyhow!("invalid {} {:?}: expected 1, 0, true or false", name, val)),
+  }
+}
+
+// how often to do something, in ms, 0 is rejected rather than running it nonstop
+pub fn interval(name: &str, default_ms: u64) -> Result<Duration> {
+  match parse_or(name, default_ms)? {
+    0 => Err(anyhow!("invalid {} 0: must be at least 1ms", name)),
+    ms => Ok(Duration::from_millis(ms)),
+  }
+}
@@ -1,4 +1,4 @@
-use super::MsgBody;
+use super::{Error, MsgBody};
 
 pub trait CRDT {
   type Element;
@@ -6,13 +6,14 @@ pub trait CRDT {
 
   fn init() -> Self;
 
-  fn add(&mut self, val: Self::Element);
+  // fails if the element can't be applied, e.g. a counter would overflow
+  fn add(&mut self, val: Self::Element) -> Result<(), Error>;
 
   // final value
-  fn read(&self) -> Self::Value;
+  fn read(&self) -> Result<Self::Value, Error>;
 
   // merge
   fn merge(&mut self, other: &Self);
   fn from_msg_body(_: &MsgBody) -> Self;
-  fn into_msg_body(&self) -> MsgBody;
+  fn to_msg_body(&self) -> MsgBody;
 }
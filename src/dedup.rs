@@ -0,0 +1,81 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::{Message, NodeID};
+
+// what's known about a request by its `(src, msg_id)`
+#[derive(Debug, Clone)]
+pub enum Seen {
+  // first time, it's been recorded
+  New,
+  // a retry of a request that hasn't been replied to yet
+  InFlight,
+  // a retry, with the reply sent the first time
+  Replied(Box<Message>),
+}
+
+// remembers the last `capacity` requests and their replies, so a retried
+// request gets the original reply instead of being applied twice
+// the oldest requests are forgotten first
+pub struct DedupTable {
+  capacity: usize,
+  entries: HashMap<(NodeID, u64), Option<Box<Message>>>,
+  order: VecDeque<(NodeID, u64)>,
+}
+
+impl DedupTable {
+  pub fn new(capacity: usize) -> Self {
+    DedupTable {
+      capacity,
+      entries: HashMap::new(),
+      order: VecDeque::new(),
+    }
+  }
+
+  pub fn check(&mut self, src: &str, msg_id: u64) -> Seen {
+    let key = (src.to_owned(), msg_id);
+    match self.entries.get(&key) {
+      Some(Some(reply)) => return Seen::Replied(reply.clone()),
+      Some(None) => return Seen::InFlight,
+      None => {}
+    }
+
+    if self.capacity == 0 {
+      return Seen::New;
+    }
+    while self.order.len() >= self.capacity {
+      if let Some(old) = self.order.pop_front() {
+        self.entries.remove(&old);
+      }
+    }
+    self.entries.insert(key.clone(), None);
+    self.order.push_back(key);
+    Seen::New
+  }
+
+  // keeps `reply` for retries of the request it answers, if that's still remembered
+  pub fn replied(&mut self, reply: &Message) {
+    if let Some(id) = reply.body.in_reply_to {
+      if let Some(entry) = self.entries.get_mut(&(reply.dest.clone(), id)) {
+        *entry = Some(Box::new(reply.clone()));
+      }
+    }
+  }
+
+  // forgets a request that was handled without a reply, e.g. it failed,
+  // so a retry is handled again rather than dropped as in flight
+  pub fn forget(&mut self, src: &str, msg_id: u64) {
+    let key = (src.to_owned(), msg_id);
+    if let Some(None) = self.entries.get(&key) {
+      self.entries.remove(&key);
+      self.order.retain(|k| *k != key);
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+}
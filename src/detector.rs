@@ -0,0 +1,142 @@
+use anyhow::Result;
+use std::{
+  collections::{BTreeSet, HashMap, VecDeque},
+  time::{Duration, Instant},
+};
+
+use super::{config, NodeID};
+
+// what's been heard from one peer
+struct History {
+  // anything at all
+  last: Instant,
+  last_heartbeat: Instant,
+  // between heartbeats, in ms, the latest `window`
+  intervals: VecDeque<f64>,
+}
+
+// phi accrual failure detector, Hayashibara et al.
+// rather than a fixed timeout, the time since a peer's last heartbeat is
+// weighed against how regularly its heartbeats arrived so far: phi is
+// -log10 of the chance a heartbeat is still coming, the peer is suspected
+// once it's above `threshold`
+// any message from a peer shows it's alive, but only heartbeats are timed, as
+// other traffic comes in bursts that would make silences look suspicious
+pub struct FailureDetector {
+  threshold: f64,
+  // how often peers send heartbeats
+  heartbeat: Duration,
+  window: usize,
+  // keeps a very regular peer from being suspected on the slightest delay
+  min_std_dev: Duration,
+  peers: HashMap<NodeID, History>,
+  suspected: BTreeSet<NodeID>,
+}
+
+impl FailureDetector {
+  pub fn new(threshold: f64, heartbeat: Duration) -> Self {
+    FailureDetector {
+      threshold,
+      heartbeat,
+      window: 100,
+      min_std_dev: Duration::from_millis(100),
+      peers: HashMap::new(),
+      suspected: BTreeSet::new(),
+    }
+  }
+
+  // reads `--phi-threshold`, 8 by default, see `config::lookup`
+  pub fn from_env(heartbeat: Duration) -> Result<Self> {
+    Ok(FailureDetector::new(config::parse_or("phi-threshold", 8.0)?, heartbeat))
+  }
+
+  pub fn heartbeat_interval(&self) -> Duration {
+    self.heartbeat
+  }
+
+  // a heartbeat arrived from `node`, true if it was suspected until now
+  // the first one starts watching it, as if heartbeats had been coming on time
+  pub fn heartbeat(&mut self, node: &str, now: Instant) -> bool {
+    let window = self.window;
+    match self.peers.get_mut(node) {
+      Some(h) => {
+        let interval = now.saturating_duration_since(h.last_heartbeat).as_secs_f64() * 1_000.0;
+        if h.intervals.len() == window {
+          h.intervals.pop_front();
+        }
+        h.intervals.push_back(interval);
+        h.last_heartbeat = now;
+      }
+      None => self.watch(node, now),
+    }
+    self.seen(node, now)
+  }
+
+  // anything else arrived from `node`, true if it was suspected until now
+  pub fn seen(&mut self, node: &str, now: Instant) -> bool {
+    match self.peers.get_mut(node) {
+      Some(h) => h.last = h.last.max(now),
+      None => self.watch(node, now),
+    }
+    self.suspected.remove(node)
+  }
+
+  fn watch(&mut self, node: &str, now: Instant) {
+    let mean = self.heartbeat.as_secs_f64() * 1_000.0;
+    self.peers.insert(
+      node.to_owned(),
+      History {
+        last: now,
+        last_heartbeat: now,
+        intervals: vec![mean * 0.75, mean * 1.25].into(),
+      },
+    );
+  }
+
+  // how sure we are `node` failed, `None` if it's not watched
+  pub fn phi(&self, node: &str, now: Instant) -> Option<f64> {
+    let h = self.peers.get(node)?;
+    let n = h.intervals.len() as f64;
+    let mean = h.intervals.iter().sum::<f64>() / n;
+    let var = h.intervals.iter().map(|i| (i - mean).powi(2)).sum::<f64>() / n;
+    let std_dev = var.sqrt().max(self.min_std_dev.as_secs_f64() * 1_000.0);
+
+    let elapsed = now.saturating_duration_since(h.last).as_secs_f64() * 1_000.0;
+    Some(phi(elapsed, mean, std_dev))
+  }
+
+  // as of the last `poll`
+  pub fn is_suspected(&self, node: &str) -> bool {
+    self.suspected.contains(node)
+  }
+
+  pub fn suspected(&self) -> impl Iterator<Item = &NodeID> {
+    self.suspected.iter()
+  }
+
+  // peers that became suspected since the last poll
+  pub fn poll(&mut self, now: Instant) -> Vec<NodeID> {
+    let mut newly = vec![];
+    for node in self.peers.keys() {
+      if !self.suspected.contains(node) && self.phi(node, now).is_some_and(|p| p > self.threshold) {
+        newly.push(node.clone());
+      }
+    }
+
+    newly.sort();
+    self.suspected.extend(newly.iter().cloned());
+    newly
+  }
+}
+
+// -log10 of the chance of waiting more than `elapsed` for the next heartbeat,
+// with intervals normally distributed, using a logistic approximation of the CDF
+fn phi(elapsed: f64, mean: f64, std_dev: f64) -> f64 {
+  let y = (elapsed - mean) / std_dev;
+  let e = (-y * (1.5976 + 0.070566 * y * y)).exp();
+  if elapsed > mean {
+    -(e / (1.0 + e)).log10()
+  } else {
+    -(1.0 - 1.0 / (1.0 + e)).log10()
+  }
+}
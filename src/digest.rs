@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::hash_map::DefaultHasher,
+  hash::{Hash, Hasher},
+};
+
+// number of hash-buckets a set is split into when digesting
+pub const BUCKETS: usize = 64;
+
+// compact summary of a set for anti-entropy, a two-level Merkle tree:
+// each bucket hash combines the hashes of the elements falling into it,
+// and the root hashes all the buckets
+// when the roots differ, the bucket hashes tell which parts need transferring
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Digest {
+  pub count: u64,
+  pub root: u64,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub buckets: Vec<u64>,
+}
+
+// stable across nodes running the same binary, as `DefaultHasher::new()` uses fixed keys
+pub fn hash_of<T: Hash + ?Sized>(elem: &T) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  elem.hash(&mut hasher);
+  hasher.finish()
+}
+
+pub fn bucket_of<T: Hash + ?Sized>(elem: &T) -> usize {
+  (hash_of(elem) % BUCKETS as u64) as usize
+}
+
+impl Digest {
+  pub fn of<'a, T: Hash + 'a>(elems: impl IntoIterator<Item = &'a T>) -> Self {
+    let mut count = 0;
+    let mut buckets = vec![0u64; BUCKETS];
+    for elem in elems {
+      let h = hash_of(elem);
+      // wrapping sum is order independent, so no need to sort the elements
+      let b = &mut buckets[(h % BUCKETS as u64) as usize];
+      *b = b.wrapping_add(h);
+      count += 1;
+    }
+
+    Digest {
+      count,
+      root: hash_of(&buckets),
+      buckets,
+    }
+  }
+
+  // only count and root, to check if two sets are equal
+  pub fn summary(&self) -> Self {
+    Digest {
+      count: self.count,
+      root: self.root,
+      buckets: vec![],
+    }
+  }
+
+  pub fn matches(&self, other: &Digest) -> bool {
+    self.count == other.count && self.root == other.root
+  }
+
+  // indices of the buckets that differ, both digests must have buckets
+  pub fn diff(&self, other: &Digest) -> Vec<usize> {
+    self
+      .buckets
+      .iter()
+      .zip(other.buckets.iter())
+      .enumerate()
+      .filter(|(_, (a, b))| a != b)
+      .map(|(i, _)| i)
+      .collect()
+  }
+}
@@ -0,0 +1,108 @@
+use std::{convert::TryFrom, fmt};
+
+use super::MsgBody;
+
+// error codes defined by the Maelstrom protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+  Timeout = 0,
+  NodeNotFound = 1,
+  NotSupported = 10,
+  TemporarilyUnavailable = 11,
+  MalformedRequest = 12,
+  Crash = 13,
+  Abort = 14,
+  KeyDoesNotExist = 20,
+  KeyAlreadyExists = 21,
+  PreconditionFailed = 22,
+  TxnConflict = 30,
+}
+
+impl TryFrom<u64> for ErrorCode {
+  type Error = u64;
+
+  fn try_from(code: u64) -> Result<Self, u64> {
+    use ErrorCode::*;
+    [
+      Timeout,
+      NodeNotFound,
+      NotSupported,
+      TemporarilyUnavailable,
+      MalformedRequest,
+      Crash,
+      Abort,
+      KeyDoesNotExist,
+      KeyAlreadyExists,
+      PreconditionFailed,
+      TxnConflict,
+    ]
+    .iter()
+    .copied()
+    .find(|c| *c as u64 == code)
+    .ok_or(code)
+  }
+}
+
+// an error that can be sent back to the client as an `error` message
+#[derive(Debug, Clone)]
+pub struct Error {
+  pub code: ErrorCode,
+  pub text: String,
+}
+
+impl Error {
+  pub fn new(code: ErrorCode, text: impl Into<String>) -> Self {
+    Error {
+      code,
+      text: text.into(),
+    }
+  }
+
+  // the error in an `error` reply, codes we don't know are taken as a crash,
+  // as it's indefinite whether the request took effect
+  pub fn from_reply(body: &MsgBody) -> Option<Self> {
+    if body.typ != "error" {
+      return None;
+    }
+
+    let code = body.code.unwrap_or(ErrorCode::Crash as u64);
+    Some(Error::new(
+      ErrorCode::try_from(code).unwrap_or(ErrorCode::Crash),
+      body.text.clone().unwrap_or_default(),
+    ))
+  }
+
+  // any error, to reply with, ours are kept, the rest are a crash as it's
+  // indefinite whether the request took effect
+  pub fn from_any(e: anyhow::Error) -> Self {
+    match e.downcast::<Error>() {
+      Ok(e) => e,
+      Err(e) => Error::new(ErrorCode::Crash, format!("{:#}", e)),
+    }
+  }
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?} ({}): {}", self.code, self.code as u64, self.text)
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl MsgBody {
+  pub fn error(code: ErrorCode, text: impl Into<String>) -> Self {
+    MsgBody {
+      typ: "error".to_owned(),
+      code: Some(code as u64),
+      text: Some(text.into()),
+      ..Default::default()
+    }
+  }
+}
+
+impl From<Error> for MsgBody {
+  fn from(err: Error) -> Self {
+    MsgBody::error(err.code, err.text)
+  }
+}
@@ -0,0 +1,81 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  future::Future,
+  pin::Pin,
+  sync::{Arc, Mutex},
+  task::{Context, Poll, Wake, Waker},
+};
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+struct TaskWaker {
+  id: u64,
+  ready: Arc<Mutex<VecDeque<u64>>>,
+}
+
+impl Wake for TaskWaker {
+  fn wake(self: Arc<Self>) {
+    self.ready.lock().unwrap().push_back(self.id);
+  }
+}
+
+// runs futures on the current thread, it doesn't block or do I/O itself:
+// whoever drives it calls `run_ready` after something may have woken a task
+#[derive(Default)]
+pub struct Executor {
+  next_id: u64,
+  tasks: HashMap<u64, Task>,
+  ready: Arc<Mutex<VecDeque<u64>>>,
+}
+
+impl Executor {
+  pub fn spawn(&mut self, fut: impl Future<Output = ()> + 'static) {
+    let id = self.next_id;
+    self.next_id += 1;
+    self.tasks.insert(id, Box::pin(fut));
+    self.ready.lock().unwrap().push_back(id);
+  }
+
+  // polls woken tasks until none are left
+  pub fn run_ready(&mut self) {
+    loop {
+      let id = match self.ready.lock().unwrap().pop_front() {
+        Some(id) => id,
+        None => return,
+      };
+      // woken more than once, or already done
+      let mut task = match self.tasks.remove(&id) {
+        Some(task) => task,
+        None => continue,
+      };
+
+      let waker = Waker::from(Arc::new(TaskWaker {
+        id,
+        ready: self.ready.clone(),
+      }));
+      if task.as_mut().poll(&mut Context::from_waker(&waker)).is_pending() {
+        self.tasks.insert(id, task);
+      }
+    }
+  }
+
+  // tasks that haven't finished
+  pub fn len(&self) -> usize {
+    self.tasks.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.tasks.is_empty()
+  }
+}
+
+// resolves once `f` returns `Ready`, for futures built around a poll function
+pub(crate) struct PollFn<F>(pub(crate) F);
+
+impl<T, F: FnMut(&mut Context) -> Poll<T> + Unpin> Future for PollFn<F> {
+  type Output = T;
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+    (self.0)(cx)
+  }
+}
@@ -0,0 +1,138 @@
+use anyhow::{anyhow, Result};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::{str::FromStr, time::Duration};
+
+use super::{config, Handler, Message, MsgBody, Node, NodeID, CRDT};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GossipMode {
+  // send our state to the peer
+  Push,
+  // ask the peer for its state
+  Pull,
+  // send our state and get theirs back
+  PushPull,
+}
+
+impl GossipMode {
+  pub fn pushes(self) -> bool {
+    self != GossipMode::Pull
+  }
+
+  pub fn pulls(self) -> bool {
+    self != GossipMode::Push
+  }
+}
+
+impl FromStr for GossipMode {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "push" => Ok(GossipMode::Push),
+      "pull" => Ok(GossipMode::Pull),
+      "push-pull" => Ok(GossipMode::PushPull),
+      _ => Err(anyhow!("expected push, pull or push-pull")),
+    }
+  }
+}
+
+// replication schedule of the CRDT nodes
+#[derive(Debug, Clone)]
+pub struct GossipConfig {
+  pub interval: Duration,
+  // peers to gossip with each round, all of them if `None`
+  pub fanout: Option<usize>,
+  pub mode: GossipMode,
+  // also push to `fanout` peers as soon as the local state changes
+  pub push_on_write: bool,
+}
+
+impl Default for GossipConfig {
+  fn default() -> Self {
+    GossipConfig {
+      interval: Duration::from_millis(2_000),
+      fanout: None,
+      mode: GossipMode::Push,
+      push_on_write: false,
+    }
+  }
+}
+
+impl GossipConfig {
+  // reads `--gossip-interval` (ms), `--gossip-fanout` (0 for all peers),
+  // `--gossip-mode` and `--push-on-write`, see `config::lookup`
+  pub fn from_env() -> Result<Self> {
+    let default = GossipConfig::default();
+    let fanout = config::parse_or("gossip-fanout", 0)?;
+
+    Ok(GossipConfig {
+      interval: config::interval("gossip-interval", default.interval.as_millis() as u64)?,
+      fanout: if fanout == 0 { None } else { Some(fanout) },
+      mode: config::parse_or("gossip-mode", default.mode)?,
+      push_on_write: config::flag("push-on-write", default.push_on_write)?,
+    })
+  }
+
+  // peers to gossip with this round, picked at random when there are more than `fanout`
+  pub fn targets(&self, peers: &[NodeID]) -> Vec<NodeID> {
+    match self.fanout {
+      Some(k) if k < peers.len() => peers.choose_multiple(&mut rand::thread_rng(), k).cloned().collect(),
+      _ => peers.to_vec(),
+    }
+  }
+}
+
+// the replication the counter nodes share, their whole state in `replicate`
+impl GossipConfig {
+  // a round, pulling only needs to ask for their state
+  pub fn gossip<C: CRDT, S: Handler>(&self, node: &mut Node<S>, crdt: &C) -> Result<()> {
+    let mut bd = if self.mode.pushes() {
+      replicate_body(crdt)
+    } else {
+      MsgBody {
+        typ: "replicate".to_owned(),
+        ..Default::default()
+      }
+    };
+    bd.mode = Some(self.mode);
+
+    for dest in self.targets(&node.live_nodes()) {
+      node.send(&dest, bd.clone())?;
+    }
+    Ok(())
+  }
+
+  // after the local state changed
+  pub fn written<C: CRDT, S: Handler>(&self, node: &mut Node<S>, crdt: &C) -> Result<()> {
+    if self.push_on_write {
+      let bd = replicate_body(crdt);
+      for dest in self.targets(&node.live_nodes()) {
+        node.send(&dest, bd.clone())?;
+      }
+    }
+    Ok(())
+  }
+
+  // merges a `replicate`, and answers it with ours if they pull
+  pub fn replicated<C: CRDT, S: Handler>(&self, node: &mut Node<S>, msg: &Message, crdt: &mut C) -> Result<()> {
+    // pull requests carry no state
+    if msg.body.mode.is_none_or(GossipMode::pushes) {
+      crdt.merge(&C::from_msg_body(&msg.body));
+    }
+
+    if msg.body.mode.is_some_and(GossipMode::pulls) {
+      node.send(&msg.src, replicate_body(crdt))?;
+    }
+    Ok(())
+  }
+}
+
+fn replicate_body<C: CRDT>(crdt: &C) -> MsgBody {
+  MsgBody {
+    typ: "replicate".to_owned(),
+    ..crdt.to_msg_body()
+  }
+}
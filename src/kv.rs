@@ -0,0 +1,81 @@
+use anyhow::Result;
+use serde_json::Value;
+
+use super::{AsyncContext, Error, ErrorCode, MsgBody};
+
+// a client for Maelstrom's key-value services, `lin-kv`, `seq-kv` and `lww-kv`
+#[derive(Clone)]
+pub struct Kv {
+  ctx: AsyncContext,
+  service: &'static str,
+}
+
+impl Kv {
+  pub fn new(ctx: &AsyncContext, service: &'static str) -> Self {
+    Kv {
+      ctx: ctx.clone(),
+      service,
+    }
+  }
+
+  pub fn lin(ctx: &AsyncContext) -> Self {
+    Kv::new(ctx, "lin-kv")
+  }
+
+  pub fn seq(ctx: &AsyncContext) -> Self {
+    Kv::new(ctx, "seq-kv")
+  }
+
+  pub fn lww(ctx: &AsyncContext) -> Self {
+    Kv::new(ctx, "lww-kv")
+  }
+
+  // `None` if the key doesn't exist
+  pub async fn read(&self, key: impl Into<Value>) -> Result<Option<Value>> {
+    let read = MsgBody {
+      typ: "read".to_owned(),
+      key: Some(key.into()),
+      ..Default::default()
+    };
+
+    match self.ctx.call(self.service, read).await {
+      Ok(resp) => Ok(resp.body.value),
+      Err(e) => match e.downcast::<Error>() {
+        Ok(e) if e.code == ErrorCode::KeyDoesNotExist => Ok(None),
+        Ok(e) => Err(e.into()),
+        Err(e) => Err(e),
+      },
+    }
+  }
+
+  pub async fn write(&self, key: impl Into<Value>, value: impl Into<Value>) -> Result<()> {
+    let write = MsgBody {
+      typ: "write".to_owned(),
+      key: Some(key.into()),
+      value: Some(value.into()),
+      ..Default::default()
+    };
+
+    self.ctx.call(self.service, write).await.map(|_| ())
+  }
+
+  // fails with `PreconditionFailed` if the value isn't `from`
+  pub async fn cas(
+    &self,
+    key: impl Into<Value>,
+    from: impl Into<Value>,
+    to: impl Into<Value>,
+    create_if_not_exists: bool,
+  ) -> Result<()> {
+    let cas = MsgBody {
+      typ: "cas".to_owned(),
+      key: Some(key.into()),
+      from: Some(from.into()),
+      to: Some(to.into()),
+      create_if_not_exists: Some(create_if_not_exists),
+      ..Default::default()
+    };
+
+    self.ctx.call(self.service, cas).await.map(|_| ())
+  }
+}
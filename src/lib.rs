@@ -9,7 +9,13 @@ pub struct Message {
   pub body: MsgBody,
 }
 
-type NodeID = String;
+pub type NodeID = String;
+
+// per-node count in the counter CRDTs, unbounded with the `bigint` feature
+#[cfg(not(feature = "bigint"))]
+pub type Count = u64;
+#[cfg(feature = "bigint")]
+pub type Count = num_bigint::BigUint;
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MsgBody {
@@ -34,7 +40,7 @@ pub struct MsgBody {
   pub echo: Option<Value>,
   // broadcast
   #[serde(skip_serializing_if = "Option::is_none")]
-  pub topology: Option<HashMap<NodeID, Vec<NodeID>>>,
+  pub topology: Option<Topology>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub message: Option<Value>,
   #[serde(skip_serializing_if = "Option::is_none")]
@@ -45,7 +51,14 @@ pub struct MsgBody {
   #[serde(skip_serializing_if = "Option::is_none")]
   pub value: Option<Value>,
   #[serde(skip_serializing_if = "Option::is_none")]
-  pub set: Option<HashSet<u64>>,
+  pub set: Option<HashSet<CanonicalValue>>,
+  // anti-entropy
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub digest: Option<Digest>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub buckets: Option<Vec<usize>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub mode: Option<GossipMode>,
   // g-counter
   #[serde(skip_serializing_if = "Option::is_none")]
   pub delta: Option<Value>,
@@ -53,7 +66,7 @@ pub struct MsgBody {
   pub counters: Option<HashMap<String, u64>>,
   // pn-counter
   #[serde(skip_serializing_if = "Option::is_none")]
-  pub pn_counters: Option<(HashMap<String, u64>, HashMap<String, u64>)>,
+  pub pn_counters: Option<(HashMap<String, Count>, HashMap<String, Count>)>,
   // txn-list-append
   // a transaction is a list of µ-op
   // each µ-op is [function, key, value]
@@ -68,7 +81,63 @@ pub struct MsgBody {
   pub to: Option<Value>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub create_if_not_exists: Option<bool>,
+  // lin-tso
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub ts: Option<u64>,
+  // logical clocks, for any message to carry
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub lamport: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub vclock: Option<VectorClock>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub hlc: Option<HlcTimestamp>,
 }
 
+mod async_node;
+pub use async_node::*;
+mod clock;
+pub use clock::*;
+mod concurrent;
+pub use concurrent::*;
+pub mod config;
 mod crdt;
 pub use crdt::*;
+mod dedup;
+pub use dedup::*;
+mod detector;
+pub use detector::*;
+mod digest;
+pub use digest::*;
+mod error;
+pub use error::*;
+mod executor;
+pub use executor::Executor;
+mod gossip;
+pub use gossip::*;
+mod kv;
+pub use kv::*;
+pub mod log;
+mod node;
+pub use node::*;
+mod output;
+pub use output::*;
+mod plumtree;
+pub use plumtree::*;
+mod pool;
+pub use pool::*;
+mod quorum;
+pub use quorum::*;
+mod record;
+pub use record::*;
+mod retry;
+pub use retry::*;
+mod ring;
+pub use ring::*;
+mod topology;
+pub use topology::*;
+mod transport;
+pub use transport::*;
+mod tso;
+pub use tso::*;
+mod value;
+pub use value::*;
@@ -0,0 +1,180 @@
+use serde_json::json;
+use std::{
+  fmt,
+  io::{self, Write},
+  str::FromStr,
+  sync::{Mutex, OnceLock},
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{config, Message};
+
+// logging to stderr, Maelstrom keeps each node's stderr in its log file
+// configured like the rest, with `--log-level`/`MAELSTROM_LOG_LEVEL` (default `info`),
+// `--log-format`/`MAELSTROM_LOG_FORMAT` (`text` or `json`, one object per line),
+// and `--trace-messages`/`MAELSTROM_TRACE_MESSAGES` to log every message in and out
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+  Error,
+  Warn,
+  Info,
+  Debug,
+  Trace,
+}
+
+impl Level {
+  fn as_str(self) -> &'static str {
+    match self {
+      Level::Error => "error",
+      Level::Warn => "warn",
+      Level::Info => "info",
+      Level::Debug => "debug",
+      Level::Trace => "trace",
+    }
+  }
+}
+
+impl FromStr for Level {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> anyhow::Result<Self> {
+    match s.to_lowercase().as_str() {
+      "error" => Ok(Level::Error),
+      "warn" => Ok(Level::Warn),
+      "info" => Ok(Level::Info),
+      "debug" => Ok(Level::Debug),
+      "trace" => Ok(Level::Trace),
+      _ => Err(anyhow::anyhow!("expected error, warn, info, debug or trace")),
+    }
+  }
+}
+
+impl fmt::Display for Level {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.pad(self.as_str())
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+  Text,
+  Json,
+}
+
+impl FromStr for Format {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> anyhow::Result<Self> {
+    match s.to_lowercase().as_str() {
+      "text" => Ok(Format::Text),
+      "json" => Ok(Format::Json),
+      _ => Err(anyhow::anyhow!("expected text or json")),
+    }
+  }
+}
+
+struct Settings {
+  level: Level,
+  json: bool,
+  trace_messages: bool,
+}
+
+fn settings() -> &'static Settings {
+  static SETTINGS: OnceLock<Settings> = OnceLock::new();
+  SETTINGS.get_or_init(|| {
+    // bad settings shouldn't take the node down, say so and fall back to the defaults
+    fn or_default<T>(setting: anyhow::Result<T>, default: T) -> T {
+      setting.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        default
+      })
+    }
+
+    Settings {
+      level: or_default(config::parse_or("log-level", Level::Info), Level::Info),
+      json: or_default(config::parse_or("log-format", Format::Text), Format::Text) == Format::Json,
+      trace_messages: or_default(config::flag("trace-messages", false), false),
+    }
+  })
+}
+
+// the node id prefixed to every line, once `init` has set it
+static NODE: Mutex<String> = Mutex::new(String::new());
+
+pub fn set_node(id: &str) {
+  *NODE.lock().unwrap() = id.to_owned();
+}
+
+pub fn enabled(level: Level) -> bool {
+  level <= settings().level
+}
+
+pub fn write(level: Level, args: fmt::Arguments) {
+  if enabled(level) {
+    emit(level, &args.to_string(), None);
+  }
+}
+
+// logs a message going `dir`, `in` or `out`, if message tracing is on
+pub fn message(dir: &str, msg: &Message) {
+  if !settings().trace_messages {
+    return;
+  }
+
+  if settings().json {
+    emit(Level::Trace, dir, serde_json::to_value(msg).ok());
+  } else {
+    let raw = serde_json::to_string(msg).unwrap_or_default();
+    emit(Level::Trace, &format!("{} {}", dir, raw), None);
+  }
+}
+
+fn emit(level: Level, text: &str, msg: Option<serde_json::Value>) {
+  let node = NODE.lock().unwrap().clone();
+  let line = if settings().json {
+    let ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut line = json!({
+      "ts": ts.as_secs_f64(),
+      "level": level.as_str(),
+      "node": node,
+      "msg": text,
+    });
+    if let Some(m) = msg {
+      line["message"] = m;
+    }
+    line.to_string()
+  } else if node.is_empty() {
+    format!("{:<5} {}", level, text)
+  } else {
+    format!("{} {:<5} {}", node, level, text)
+  };
+
+  // one write per line, so lines from different threads don't interleave
+  let _ = writeln!(io::stderr().lock(), "{}", line);
+}
+
+#[macro_export]
+macro_rules! error {
+  ($($arg:tt)*) => { $crate::log::write($crate::log::Level::Error, format_args!($($arg)*)) };
+}
+
+#[macro_export]
+macro_rules! warn {
+  ($($arg:tt)*) => { $crate::log::write($crate::log::Level::Warn, format_args!($($arg)*)) };
+}
+
+#[macro_export]
+macro_rules! info {
+  ($($arg:tt)*) => { $crate::log::write($crate::log::Level::Info, format_args!($($arg)*)) };
+}
+
+#[macro_export]
+macro_rules! debug {
+  ($($arg:tt)*) => { $crate::log::write($crate::log::Level::Debug, format_args!($($arg)*)) };
+}
+
+#[macro_export]
+macro_rules! trace {
+  ($($arg:tt)*) => { $crate::log::write($crate::log::Level::Trace, format_args!($($arg)*)) };
+}
@@ -0,0 +1,630 @@
+use anyhow::Result;
+use std::{
+  cell::RefCell,
+  cmp::Reverse,
+  collections::{BinaryHeap, HashMap, VecDeque},
+  rc::Rc,
+  sync::{
+    mpsc::{self, RecvTimeoutError},
+    Arc,
+  },
+  thread,
+  time::{Duration, Instant},
+};
+
+use super::{
+  config, debug, info, log, transport, warn, DedupTable, Direction, Error, ErrorCode, FailureDetector, Message,
+  MsgBody, NodeID, Outbox, Output, Recorder, Seen, SentStats, Tally, Transport,
+};
+
+// a workload, the node state lives in the implementing type
+pub trait Handler: Sized {
+  // called once `init` has been replied to, node id and membership are set
+  fn init(&mut self, _node: &mut Node<Self>) -> Result<()> {
+    Ok(())
+  }
+
+  fn handle(&mut self, node: &mut Node<Self>, msg: Message) -> Result<()>;
+
+  // replies no RPC callback is waiting for, e.g. acks the workload tracks itself
+  // they're dropped by default, they come after their RPC timed out
+  fn handle_reply(&mut self, _node: &mut Node<Self>, msg: Message) -> Result<()> {
+    drop_reply(&msg);
+    Ok(())
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimerId(u64);
+
+// shortest time between two runs of an interval
+const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+type TaskFn<S> = Box<dyn FnMut(&mut S, &mut Node<S>) -> Result<()>>;
+type Callback<S> = Box<dyn FnOnce(&mut S, &mut Node<S>, Message) -> Result<()>>;
+type Hook<S> = Box<dyn FnOnce(&mut S, &mut Node<S>) -> Result<()>>;
+type SuspicionFn<S> = Box<dyn FnMut(&mut S, &mut Node<S>, &str, bool) -> Result<()>>;
+
+// what wakes the event loop up, besides timers
+enum Event {
+  Message(Box<Message>),
+  InputClosed,
+  OutputClosed,
+}
+
+struct Task<S> {
+  deadline: Instant,
+  every: Option<Duration>,
+  f: TaskFn<S>,
+}
+
+// the runtime a `Handler` runs in
+// messages, replies to RPCs and timers are all handled on one event loop,
+// so handlers get `&mut` access to the state without any locking
+pub struct Node<S> {
+  pub id: NodeID,
+  pub node_ids: Vec<NodeID>,
+  next_timer_id: u64,
+  timers: BinaryHeap<Reverse<(Instant, TimerId)>>,
+  tasks: HashMap<TimerId, Task<S>>,
+  // the interval being run is out of `tasks`, this tells if it cleared itself
+  running: Option<(TimerId, bool)>,
+  callbacks: HashMap<u64, Callback<S>>,
+  shutdown_hooks: Vec<Hook<S>>,
+  output: Output,
+  recorder: Option<Arc<Recorder>>,
+  // messages that arrived before `init`, handled right after it
+  pre_init: VecDeque<Message>,
+  pre_init_limit: usize,
+  // peer liveness, off unless heartbeats are configured or `detect_failures` is called
+  detector: Option<FailureDetector>,
+  // from the config, until it starts at init
+  detect_at_init: Option<FailureDetector>,
+  on_suspicion: Vec<SuspicionFn<S>>,
+  // the async and concurrent runtimes reply after `handle` returns, and settle
+  // requests with the outbox themselves once they're done with them
+  pub(crate) settles_requests: bool,
+}
+
+impl<S: Handler> Node<S> {
+  // runs the event loop on the transport picked in the config, stdio by default
+  pub fn run(state: S) -> Result<()> {
+    Node::run_with(state, transport::from_config()?)
+  }
+
+  // runs the event loop on `transport` until its input is closed,
+  // then runs the shutdown hooks and returns
+  pub fn run_with(mut state: S, mut transport: impl Transport) -> Result<()> {
+    let recorder = config::lookup("record")
+      .map(|path| Recorder::create(&path))
+      .transpose()?
+      .map(Arc::new);
+    let pre_init_limit = config::parse_or("pre-init-limit", 1_024)?;
+    // off unless asked for, only handlers that aren't idempotent need it
+    let dedup = match config::parse_or("dedup-capacity", 0)? {
+      0 => None,
+      n => Some(DedupTable::new(n)),
+    };
+
+    // 0 turns failure detection off
+    let detect_at_init = match config::parse_or("heartbeat-interval", 0)? {
+      0 => None,
+      ms => Some(FailureDetector::from_env(Duration::from_millis(ms))?),
+    };
+
+    let (events, rx) = mpsc::channel();
+    let input = transport.incoming()?;
+    let ev = events.clone();
+    thread::spawn(move || {
+      for msg in input {
+        if ev.send(Event::Message(Box::new(msg))).is_err() {
+          return;
+        }
+      }
+      let _ = ev.send(Event::InputClosed);
+    });
+
+    let mut node = Node {
+      id: NodeID::new(),
+      node_ids: vec![],
+      next_timer_id: 0,
+      timers: BinaryHeap::new(),
+      tasks: HashMap::new(),
+      running: None,
+      callbacks: HashMap::new(),
+      shutdown_hooks: vec![],
+      output: Output::new(transport.outgoing()?, recorder.clone(), dedup, move || {
+        let _ = events.send(Event::OutputClosed);
+      }),
+      recorder,
+      pre_init: VecDeque::new(),
+      pre_init_limit,
+      detector: None,
+      detect_at_init,
+      on_suspicion: vec![],
+      settles_requests: false,
+    };
+
+    loop {
+      node.fire_timers(&mut state)?;
+
+      let event = match node.timers.peek() {
+        Some(Reverse((deadline, _))) => match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+          Ok(event) => event,
+          Err(RecvTimeoutError::Timeout) => continue,
+          Err(RecvTimeoutError::Disconnected) => Event::InputClosed,
+        },
+        None => rx.recv().unwrap_or(Event::InputClosed),
+      };
+
+      let msg = match event {
+        Event::Message(msg) => *msg,
+        Event::InputClosed => {
+          info!("Input closed, shutting down");
+          break;
+        }
+        // nobody is reading what we send anymore, the transport's reader may
+        // still be blocked on its input, it goes away with the process
+        Event::OutputClosed => {
+          info!("Output closed, shutting down");
+          break;
+        }
+      };
+
+      log::message("in", &msg);
+      if let Some(r) = &node.recorder {
+        r.record(Direction::In, &msg)?;
+      }
+      node.dispatch(&mut state, msg)?;
+    }
+
+    node.shutdown(&mut state)?;
+
+    Ok(())
+  }
+
+  fn dispatch(&mut self, state: &mut S, msg: Message) -> Result<()> {
+    // anything from a peer shows it's alive, clients and services aren't watched
+    let peer = msg.src != self.id && self.node_ids.contains(&msg.src);
+    if let Some(d) = self.detector.as_mut().filter(|_| peer) {
+      let now = Instant::now();
+      let recovered = if msg.body.typ == "heartbeat" {
+        d.heartbeat(&msg.src, now)
+      } else {
+        d.seen(&msg.src, now)
+      };
+      if recovered {
+        info!("{} is back", msg.src);
+        self.suspicion_changed(state, &msg.src, false)?;
+      }
+    }
+    if msg.body.typ == "heartbeat" {
+      return Ok(());
+    }
+
+    if let Some(cb) = msg.body.in_reply_to.and_then(|id| self.callbacks.remove(&id)) {
+      return cb(state, self, msg);
+    }
+
+    if msg.body.typ == "init" {
+      self.id = msg.body.node_id.clone().unwrap();
+      self.node_ids = msg.body.node_ids.clone().unwrap();
+      log::set_node(&self.id);
+      if let Some(r) = &self.recorder {
+        r.set_node(&self.id)?;
+      }
+      info!("Node {} initialized", &self.id);
+
+      let r = MsgBody {
+        typ: "init_ok".to_owned(),
+        ..Default::default()
+      };
+      self.reply(&msg, r)?;
+      if let Some(d) = self.detect_at_init.take() {
+        self.detect_failures(d);
+      }
+      state.init(self)?;
+
+      for msg in std::mem::take(&mut self.pre_init) {
+        self.dispatch(state, msg)?;
+      }
+      return Ok(());
+    }
+
+    // handlers can count on the node id and membership being set
+    if self.id.is_empty() {
+      return self.hold(msg);
+    }
+
+    if msg.body.in_reply_to.is_some() {
+      return state.handle_reply(self, msg);
+    }
+
+    // retried requests aren't applied twice
+    match self.output.outbox().seen(&msg) {
+      Seen::New => {}
+      Seen::InFlight => {
+        debug!(
+          "Dropping retry of {} {:?} from {}, still handling it",
+          msg.body.typ, msg.body.msg_id, msg.src
+        );
+        return Ok(());
+      }
+      Seen::Replied(reply) => {
+        debug!(
+          "Replaying reply to {} {:?} from {}",
+          msg.body.typ, msg.body.msg_id, msg.src
+        );
+        return self.send_msg(&reply);
+      }
+    }
+
+    // a reply sent later from a callback isn't kept, a retry meanwhile is handled again
+    let (src, id) = (msg.src.clone(), msg.body.msg_id);
+    let res = state.handle(self, msg);
+    if !self.settles_requests {
+      self.output.outbox().settle(&src, id);
+    }
+    res
+  }
+
+  fn hold(&mut self, msg: Message) -> Result<()> {
+    if self.pre_init.len() < self.pre_init_limit {
+      self.pre_init.push_back(msg);
+      return Ok(());
+    }
+
+    // node ids aren't known yet, by Maelstrom's naming peers are `n*`
+    // only clients are told, a peer's gossip or ack can't take an error reply
+    let client = !msg.src.starts_with('n');
+    if !client || msg.body.msg_id.is_none() || msg.body.in_reply_to.is_some() {
+      warn!("Dropping {} from {}, not initialized yet", msg.body.typ, msg.src);
+      return Ok(());
+    }
+
+    warn!("Rejecting {} from {}, not initialized yet", msg.body.typ, msg.src);
+    let r = MsgBody::error(ErrorCode::TemporarilyUnavailable, "node not initialized yet");
+    self.reply(&msg, r)
+  }
+
+  // starts sending heartbeats to the other nodes and watching theirs, once
+  // initialized, calling it again only swaps the detector
+  pub fn detect_failures(&mut self, mut detector: FailureDetector) {
+    let now = Instant::now();
+    for n in self.other_nodes() {
+      detector.heartbeat(&n, now);
+    }
+
+    let every = detector.heartbeat_interval();
+    if self.detector.replace(detector).is_some() {
+      return;
+    }
+
+    self.set_interval(every, |s, node| {
+      let hb = MsgBody {
+        typ: "heartbeat".to_owned(),
+        ..Default::default()
+      };
+      for n in node.other_nodes() {
+        node.send(&n, hb.clone())?;
+      }
+
+      let suspected = match node.detector.as_mut() {
+        Some(d) => d.poll(Instant::now()),
+        None => vec![],
+      };
+      for n in suspected {
+        warn!("Suspecting {} failed", n);
+        node.suspicion_changed(s, &n, true)?;
+      }
+      Ok(())
+    });
+  }
+
+  fn suspicion_changed(&mut self, state: &mut S, peer: &str, suspected: bool) -> Result<()> {
+    // callbacks may register more callbacks
+    let mut cbs = std::mem::take(&mut self.on_suspicion);
+    let res = cbs.iter_mut().try_for_each(|cb| cb(state, self, peer, suspected));
+    cbs.append(&mut self.on_suspicion);
+    self.on_suspicion = cbs;
+    res
+  }
+}
+
+impl<S> Node<S> {
+  // all nodes but this one
+  pub fn other_nodes(&self) -> Vec<NodeID> {
+    others(&self.id, &self.node_ids)
+  }
+
+  // with failure detection on, whether `node` is thought to have failed
+  pub fn is_suspected(&self, node: &str) -> bool {
+    self.detector.as_ref().is_some_and(|d| d.is_suspected(node))
+  }
+
+  // the other nodes, but those suspected to have failed
+  pub fn live_nodes(&self) -> Vec<NodeID> {
+    self
+      .other_nodes()
+      .into_iter()
+      .filter(|n| !self.is_suspected(n))
+      .collect()
+  }
+
+  // `f` is called when a peer becomes suspected, with `true`, and when it's
+  // heard from again, with `false`
+  pub fn on_suspicion<F>(&mut self, f: F)
+  where
+    F: FnMut(&mut S, &mut Node<S>, &str, bool) -> Result<()> + 'static,
+  {
+    self.on_suspicion.push(Box::new(f));
+  }
+
+  pub fn gen_id(&mut self) -> u64 {
+    self.output.outbox().gen_id()
+  }
+
+  // for sending from other threads
+  pub fn outbox(&self) -> Outbox {
+    self.output.outbox().clone()
+  }
+
+  pub fn send(&mut self, dest: &str, body: MsgBody) -> Result<()> {
+    self.output.outbox().send_from(&self.id, dest, body)
+  }
+
+  pub fn send_msg(&mut self, msg: &Message) -> Result<()> {
+    self.output.outbox().send(msg.clone())
+  }
+
+  pub fn sent(&self) -> SentStats {
+    self.output.stats()
+  }
+
+  // see `Outbox::reply`
+  pub fn reply(&mut self, origin: &Message, resp_body: MsgBody) -> Result<()> {
+    self.output.outbox().reply(origin, resp_body)
+  }
+
+  // sends `body` with a fresh `msg_id`, `cb` is called with the reply when it arrives
+  pub fn rpc<F>(&mut self, dest: &str, mut body: MsgBody, cb: F) -> Result<()>
+  where
+    F: FnOnce(&mut S, &mut Node<S>, Message) -> Result<()> + 'static,
+  {
+    let id = self.gen_id();
+    body.msg_id = Some(id);
+    self.callbacks.insert(id, Box::new(cb));
+
+    self.send(dest, body)
+  }
+
+  // like `rpc`, `cb` gets `None` if there's no reply within `timeout`
+  pub fn rpc_or_timeout<F>(&mut self, dest: &str, mut body: MsgBody, timeout: Duration, cb: F) -> Result<()>
+  where
+    F: FnOnce(&mut S, &mut Node<S>, Option<Message>) -> Result<()> + 'static,
+  {
+    let id = self.gen_id();
+    body.msg_id = Some(id);
+
+    // whichever of the reply and the timer comes first takes `cb`
+    let cb = Rc::new(RefCell::new(Some(cb)));
+    let on_timeout = cb.clone();
+    let timer = self.set_timeout(timeout, move |s, node| {
+      node.callbacks.remove(&id);
+      match on_timeout.borrow_mut().take() {
+        Some(cb) => cb(s, node, None),
+        None => Ok(()),
+      }
+    });
+    self.callbacks.insert(
+      id,
+      Box::new(move |s, node, reply| {
+        node.clear_timer(timer);
+        match cb.borrow_mut().take() {
+          Some(cb) => cb(s, node, Some(reply)),
+          None => Ok(()),
+        }
+      }),
+    );
+
+    self.send(dest, body)
+  }
+
+  // sends `body` to each of `nodes`, `cb` gets the successful replies once
+  // `need` of them are in, or an error once that can't happen in `timeout`
+  // later replies are ignored
+  pub fn quorum<F>(&mut self, nodes: &[NodeID], body: MsgBody, need: usize, timeout: Duration, cb: F) -> Result<()>
+  where
+    F: FnOnce(&mut S, &mut Node<S>, std::result::Result<Vec<Message>, Error>) -> Result<()> + 'static,
+  {
+    let tally = Rc::new(RefCell::new((Tally::new(need, nodes.len()), Some(cb))));
+    // e.g. nobody to ask, it's decided before any reply
+    if tally.borrow().0.ready() {
+      let tally = tally.clone();
+      self.set_timeout(Duration::ZERO, move |s, node| decide(&tally, s, node));
+    }
+
+    for n in nodes {
+      let tally = tally.clone();
+      self.rpc_or_timeout(n, body.clone(), timeout, move |s, node, reply| {
+        tally.borrow_mut().0.add(reply);
+        decide(&tally, s, node)
+      })?;
+    }
+
+    Ok(())
+  }
+
+  // hands `msg` to `to` and relays its reply back to the sender as ours,
+  // no reply within `timeout` is answered with temporarily-unavailable
+  pub fn forward(&mut self, msg: &Message, to: &str, timeout: Duration) -> Result<()> {
+    let origin = msg.clone();
+    let peer = to.to_owned();
+    self.rpc_or_timeout(to, msg.body.clone(), timeout, move |_, node, reply| {
+      node.reply(&origin, relayed(reply, &peer))
+    })
+  }
+
+  // runs `f` once on the event loop, after `after`
+  pub fn set_timeout<F>(&mut self, after: Duration, f: F) -> TimerId
+  where
+    F: FnOnce(&mut S, &mut Node<S>) -> Result<()> + 'static,
+  {
+    let mut f = Some(f);
+    self.schedule(after, None, Box::new(move |s, n| (f.take().unwrap())(s, n)))
+  }
+
+  // runs `f` on the event loop every `every`, until cleared
+  pub fn set_interval<F>(&mut self, every: Duration, f: F) -> TimerId
+  where
+    F: FnMut(&mut S, &mut Node<S>) -> Result<()> + 'static,
+  {
+    self.schedule(every, Some(every), Box::new(f))
+  }
+
+  // runs once the input is closed, in the order registered, before the timers stop
+  pub fn on_shutdown<F>(&mut self, f: F)
+  where
+    F: FnOnce(&mut S, &mut Node<S>) -> Result<()> + 'static,
+  {
+    self.shutdown_hooks.push(Box::new(f));
+  }
+
+  pub fn clear_timer(&mut self, id: TimerId) {
+    self.tasks.remove(&id);
+    if let Some((running, cleared)) = self.running.as_mut() {
+      *cleared |= *running == id;
+    }
+  }
+
+  fn schedule(&mut self, after: Duration, every: Option<Duration>, f: TaskFn<S>) -> TimerId {
+    let id = TimerId(self.next_timer_id);
+    self.next_timer_id += 1;
+
+    let deadline = Instant::now() + after;
+    self.timers.push(Reverse((deadline, id)));
+    self.tasks.insert(id, Task { deadline, every, f });
+    id
+  }
+
+  fn shutdown(&mut self, state: &mut S) -> Result<()> {
+    // hooks may register more hooks
+    while !self.shutdown_hooks.is_empty() {
+      for hook in std::mem::take(&mut self.shutdown_hooks) {
+        hook(state, self)?;
+      }
+    }
+
+    self.timers.clear();
+    self.tasks.clear();
+    self.on_suspicion.clear();
+    // nobody is left to reply
+    self.callbacks.clear();
+
+    self.output.close();
+    let sent = self.output.stats();
+    info!(
+      "Sent {} messages, dropped {}: {:?}",
+      sent.total, sent.dropped, sent.by_type
+    );
+    Ok(())
+  }
+
+  fn fire_timers(&mut self, state: &mut S) -> Result<()> {
+    let now = Instant::now();
+    while let Some(Reverse((deadline, id))) = self.timers.peek().cloned() {
+      if deadline > now {
+        break;
+      }
+      self.timers.pop();
+
+      // skip cleared or rescheduled timers
+      let mut task = match self.tasks.remove(&id) {
+        Some(task) if task.deadline == deadline => task,
+        Some(task) => {
+          self.tasks.insert(id, task);
+          continue;
+        }
+        None => continue,
+      };
+
+      self.running = Some((id, false));
+      let res = (task.f)(state, self);
+      let cleared = self.running.take().is_some_and(|(_, cleared)| cleared);
+      res?;
+
+      if let Some(every) = task.every.filter(|_| !cleared) {
+        task.deadline = deadline + every;
+        // don't try to catch up if we fell behind
+        if task.deadline < now {
+          task.deadline = now + every;
+        }
+        // nor run again in this loop, a zero interval would never let go
+        task.deadline = task.deadline.max(now + MIN_INTERVAL);
+        self.timers.push(Reverse((task.deadline, id)));
+        self.tasks.insert(id, task);
+      }
+    }
+
+    Ok(())
+  }
+}
+
+// what happens to replies nobody is waiting for, in every runtime
+pub(crate) fn drop_reply(msg: &Message) {
+  debug!(
+    "Dropping {} from {}, nothing is waiting for a reply to {:?}",
+    msg.body.typ, msg.src, msg.body.in_reply_to
+  );
+}
+
+// all of `node_ids` but `id`, for every runtime's `other_nodes`
+pub(crate) fn others(id: &str, node_ids: &[NodeID]) -> Vec<NodeID> {
+  node_ids.iter().filter(|n| *n != id).cloned().collect()
+}
+
+// answers a request the blocking or async runtimes forwarded to `peer`, given
+// how their RPC went, a timeout is temporarily-unavailable
+pub(crate) fn forwarded(outbox: &Outbox, msg: &Message, peer: &str, rpc: Result<Message>) -> Result<()> {
+  let reply = match rpc {
+    Ok(reply) => Some(reply),
+    Err(e) if e.downcast_ref::<Error>().is_some_and(|e| e.code == ErrorCode::Timeout) => None,
+    Err(e) => return Err(e),
+  };
+  outbox.reply(msg, relayed(reply, peer))
+}
+
+// what to answer a forwarded request with, given the peer's reply
+pub(crate) fn relayed(reply: Option<Message>, peer: &str) -> MsgBody {
+  match reply {
+    Some(reply) => MsgBody {
+      // ours is filled in when it's sent on
+      msg_id: None,
+      in_reply_to: None,
+      ..reply.body
+    },
+    None => MsgBody::error(ErrorCode::TemporarilyUnavailable, format!("no reply from {}", peer)),
+  }
+}
+
+type Decision<F> = RefCell<(Tally, Option<F>)>;
+
+// calls the quorum callback, once, if there's an outcome
+fn decide<S, F>(tally: &Decision<F>, s: &mut S, node: &mut Node<S>) -> Result<()>
+where
+  F: FnOnce(&mut S, &mut Node<S>, std::result::Result<Vec<Message>, Error>) -> Result<()>,
+{
+  let (outcome, cb) = {
+    let mut t = tally.borrow_mut();
+    (t.0.outcome(), t.1.take())
+  };
+  match (outcome, cb) {
+    (Some(outcome), Some(cb)) => cb(s, node, outcome),
+    (None, Some(cb)) => {
+      // not yet, put it back
+      tally.borrow_mut().1 = Some(cb);
+      Ok(())
+    }
+    _ => Ok(()),
+  }
+}
@@ -0,0 +1,177 @@
+use anyhow::Result;
+use std::{
+  collections::HashMap,
+  io,
+  sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    mpsc::{self, Sender},
+    Arc, Mutex,
+  },
+  thread::{self, JoinHandle},
+};
+
+use super::{error, log, DedupTable, Direction, Message, MsgBody, Outgoing, Recorder, Seen};
+
+// how many messages went out, by body type
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SentStats {
+  pub total: u64,
+  pub by_type: HashMap<String, u64>,
+  // dropped because they couldn't be sent or the output was closed
+  pub dropped: u64,
+}
+
+#[derive(Default)]
+struct Shared {
+  stats: Mutex<SentStats>,
+  // the output is gone, e.g. the reading end of stdout was closed
+  closed: AtomicBool,
+  next_msg_id: AtomicU64,
+  // replies sent, for retried requests
+  dedup: Option<Mutex<DedupTable>>,
+}
+
+// a handle to send messages from any thread, they go through the node's writer
+#[derive(Clone)]
+pub struct Outbox {
+  // `None` stops the writer
+  tx: Sender<Option<Message>>,
+  shared: Arc<Shared>,
+  recorder: Option<Arc<Recorder>>,
+}
+
+impl Outbox {
+  // queues `msg` for writing, messages sent after the output closed are dropped
+  pub fn send(&self, msg: Message) -> Result<()> {
+    log::message("out", &msg);
+    if let Some(r) = &self.recorder {
+      r.record(Direction::Out, &msg)?;
+    }
+
+    if let Some(d) = &self.shared.dedup {
+      d.lock().unwrap().replied(&msg);
+    }
+
+    let closed = self.shared.closed.load(Ordering::SeqCst);
+    if closed || self.tx.send(Some(msg)).is_err() {
+      self.shared.stats.lock().unwrap().dropped += 1;
+    }
+    Ok(())
+  }
+
+  pub(crate) fn send_from(&self, src: &str, dest: &str, body: MsgBody) -> Result<()> {
+    self.send(Message {
+      src: src.to_owned(),
+      dest: dest.to_owned(),
+      body,
+    })
+  }
+
+  // answers `origin` as the node it was sent to, fills in `in_reply_to`,
+  // and a `msg_id` if there's none
+  pub fn reply(&self, origin: &Message, mut resp_body: MsgBody) -> Result<()> {
+    resp_body.in_reply_to = Some(origin.body.msg_id.unwrap());
+    if resp_body.msg_id.is_none() {
+      resp_body.msg_id = Some(self.gen_id());
+    }
+
+    self.send_from(&origin.dest, &origin.src, resp_body)
+  }
+
+  // msg ids are unique across the node, whichever thread sends
+  pub fn gen_id(&self) -> u64 {
+    self.shared.next_msg_id.fetch_add(1, Ordering::SeqCst)
+  }
+
+  // done handling the request `msg_id` from `src`, if it wasn't replied to
+  // a retry is handled like a new request
+  pub(crate) fn settle(&self, src: &str, msg_id: Option<u64>) {
+    if let (Some(d), Some(id)) = (&self.shared.dedup, msg_id) {
+      d.lock().unwrap().forget(src, id);
+    }
+  }
+
+  // always `New` without a dedup table, or for messages without a `msg_id`
+  pub(crate) fn seen(&self, msg: &Message) -> Seen {
+    match (&self.shared.dedup, msg.body.msg_id) {
+      (Some(d), Some(id)) => d.lock().unwrap().check(&msg.src, id),
+      _ => Seen::New,
+    }
+  }
+}
+
+// the only writer of the transport's output
+// messages are sent over a channel to one thread that serializes, writes and
+// flushes them, so lines from different senders never interleave
+pub(crate) struct Output {
+  outbox: Outbox,
+  writer: Option<JoinHandle<()>>,
+}
+
+impl Output {
+  // `on_close` is called from the writer thread if the output closes
+  pub(crate) fn new(
+    mut out: Box<dyn Outgoing>,
+    recorder: Option<Arc<Recorder>>,
+    dedup: Option<DedupTable>,
+    on_close: impl FnOnce() + Send + 'static,
+  ) -> Self {
+    let (tx, rx) = mpsc::channel::<Option<Message>>();
+    let shared = Arc::new(Shared {
+      dedup: dedup.map(Mutex::new),
+      ..Default::default()
+    });
+
+    let sh = shared.clone();
+    let writer = thread::spawn(move || {
+      while let Ok(Some(msg)) = rx.recv() {
+        match out.send(&msg) {
+          Ok(()) => {
+            let mut stats = sh.stats.lock().unwrap();
+            stats.total += 1;
+            *stats.by_type.entry(msg.body.typ).or_default() += 1;
+          }
+          Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+            sh.closed.store(true, Ordering::SeqCst);
+            // count what's left as dropped, without holding up the senders
+            sh.stats.lock().unwrap().dropped += 1 + rx.try_iter().flatten().count() as u64;
+            on_close();
+            break;
+          }
+          Err(e) => {
+            error!("Failed sending {} to {}: {}", msg.body.typ, msg.dest, e);
+            sh.stats.lock().unwrap().dropped += 1;
+          }
+        }
+      }
+    });
+
+    Output {
+      outbox: Outbox { tx, shared, recorder },
+      writer: Some(writer),
+    }
+  }
+
+  pub(crate) fn outbox(&self) -> &Outbox {
+    &self.outbox
+  }
+
+  pub(crate) fn stats(&self) -> SentStats {
+    self.outbox.shared.stats.lock().unwrap().clone()
+  }
+
+  // writes out everything queued so far and stops the writer,
+  // `Outbox`es still around drop what they send after this
+  pub(crate) fn close(&mut self) {
+    if let Some(writer) = self.writer.take() {
+      let _ = self.outbox.tx.send(None);
+      let _ = writer.join();
+    }
+  }
+}
+
+impl Drop for Output {
+  fn drop(&mut self) {
+    self.close();
+  }
+}
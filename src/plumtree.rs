@@ -0,0 +1,134 @@
+use std::{
+  collections::{BTreeSet, HashMap, VecDeque},
+  hash::Hash,
+  time::{Duration, Instant},
+};
+
+use super::NodeID;
+
+// what the node should send after a Plumtree step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlumtreeSend<T> {
+  // push the value itself
+  Eager(NodeID, T),
+  // announce having the value
+  IHave(NodeID, T),
+  // ask for missing values and make the link eager
+  Graft(NodeID, Vec<T>),
+  // make the link lazy
+  Prune(NodeID),
+}
+
+// epidemic broadcast trees, Leitão et al.
+// values are pushed along the eager links, which start as all the peers and
+// get pruned down to a spanning tree as duplicates arrive
+// lazy links only get announcements, a value announced but not received
+// within `graft_timeout` grafts the announcer's link back into the tree
+// values identify themselves, so they must be unique per broadcast
+pub struct Plumtree<T> {
+  eager: BTreeSet<NodeID>,
+  lazy: BTreeSet<NodeID>,
+  // announced but not received values: when to graft, and who announced them
+  missing: HashMap<T, (Instant, VecDeque<NodeID>)>,
+  graft_timeout: Duration,
+}
+
+impl<T: Hash + Eq + Clone> Plumtree<T> {
+  pub fn new(peers: impl IntoIterator<Item = NodeID>, graft_timeout: Duration) -> Self {
+    Plumtree {
+      eager: peers.into_iter().collect(),
+      lazy: BTreeSet::new(),
+      missing: HashMap::new(),
+      graft_timeout,
+    }
+  }
+
+  // a value we didn't have arrived, from a peer or from a client if `from` is `None`
+  pub fn deliver(&mut self, value: T, from: Option<&str>) -> Vec<PlumtreeSend<T>> {
+    self.missing.remove(&value);
+    if let Some(from) = from {
+      self.make_eager(from);
+    }
+
+    let others = |peers: &BTreeSet<NodeID>| -> Vec<NodeID> {
+      peers.iter().filter(|p| Some(p.as_str()) != from).cloned().collect()
+    };
+
+    let mut sends: Vec<PlumtreeSend<T>> = others(&self.eager)
+      .into_iter()
+      .map(|p| PlumtreeSend::Eager(p, value.clone()))
+      .collect();
+    sends.extend(
+      others(&self.lazy)
+        .into_iter()
+        .map(|p| PlumtreeSend::IHave(p, value.clone())),
+    );
+    sends
+  }
+
+  // only values we already had arrived from a peer, its link is redundant
+  pub fn redundant(&mut self, from: &str) -> Vec<PlumtreeSend<T>> {
+    if self.eager.remove(from) {
+      self.lazy.insert(from.to_owned());
+      vec![PlumtreeSend::Prune(from.to_owned())]
+    } else {
+      vec![]
+    }
+  }
+
+  // a peer announced a value we don't have
+  pub fn ihave(&mut self, value: T, from: &str, now: Instant) {
+    let deadline = now + self.graft_timeout;
+    let (_, announcers) = self.missing.entry(value).or_insert_with(|| (deadline, VecDeque::new()));
+    if !announcers.iter().any(|a| a == from) {
+      announcers.push_back(from.to_owned());
+    }
+  }
+
+  // the value arrived some other way, e.g. anti-entropy, stop waiting for it
+  pub fn have(&mut self, value: &T) {
+    self.missing.remove(value);
+  }
+
+  // a peer wants values from us, keep pushing to it
+  pub fn graft(&mut self, from: &str) {
+    self.make_eager(from);
+  }
+
+  pub fn prune(&mut self, from: &str) {
+    if self.eager.remove(from) {
+      self.lazy.insert(from.to_owned());
+    }
+  }
+
+  // grafts for values that were announced but haven't arrived in time
+  // each timeout tries the next announcer
+  pub fn poll(&mut self, now: Instant) -> Vec<PlumtreeSend<T>> {
+    let mut grafts: HashMap<NodeID, Vec<T>> = HashMap::new();
+    for (value, (deadline, announcers)) in self.missing.iter_mut() {
+      if *deadline > now {
+        continue;
+      }
+
+      if let Some(peer) = announcers.pop_front() {
+        grafts.entry(peer.clone()).or_default().push(value.clone());
+        // retry with the same peer last, if nobody else announced it
+        announcers.push_back(peer);
+      }
+      *deadline = now + self.graft_timeout;
+    }
+
+    grafts
+      .into_iter()
+      .map(|(peer, values)| {
+        self.make_eager(&peer);
+        PlumtreeSend::Graft(peer, values)
+      })
+      .collect()
+  }
+
+  fn make_eager(&mut self, peer: &str) {
+    self.lazy.remove(peer);
+    self.eager.insert(peer.to_owned());
+  }
+}
@@ -0,0 +1,121 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  panic::{self, AssertUnwindSafe},
+  sync::{Arc, Condvar, Mutex},
+  thread::{self, JoinHandle},
+};
+
+use super::error;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+struct Queue {
+  ready: VecDeque<(Option<String>, Job)>,
+  // keys with a job queued or running, and the jobs waiting behind it
+  busy: HashMap<String, VecDeque<Job>>,
+  stopping: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+  queue: Mutex<Queue>,
+  wake: Condvar,
+}
+
+// runs jobs on a fixed set of threads
+// jobs with the same key run one at a time in the order submitted, a slow key
+// only holds up jobs behind it on the same key
+pub struct WorkerPool {
+  shared: Arc<Shared>,
+  workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+  pub fn new(threads: usize) -> Self {
+    let shared = Arc::new(Shared::default());
+    let workers = (0..threads.max(1))
+      .map(|_| {
+        let shared = shared.clone();
+        thread::spawn(move || work(&shared))
+      })
+      .collect();
+
+    WorkerPool { shared, workers }
+  }
+
+  // jobs without a key can run alongside anything
+  pub fn submit(&self, key: Option<String>, job: impl FnOnce() + Send + 'static) {
+    let job: Job = Box::new(job);
+    let mut q = self.shared.queue.lock().unwrap();
+    match key {
+      Some(key) => match q.busy.get_mut(&key) {
+        Some(waiting) => waiting.push_back(job),
+        None => {
+          q.busy.insert(key.clone(), VecDeque::new());
+          q.ready.push_back((Some(key), job));
+        }
+      },
+      None => q.ready.push_back((None, job)),
+    }
+    drop(q);
+    self.shared.wake.notify_one();
+  }
+
+  // runs what's been submitted and stops the threads
+  pub fn join(mut self) {
+    self.stop();
+  }
+
+  fn stop(&mut self) {
+    self.shared.queue.lock().unwrap().stopping = true;
+    self.shared.wake.notify_all();
+    for w in self.workers.drain(..) {
+      let _ = w.join();
+    }
+  }
+}
+
+impl Drop for WorkerPool {
+  fn drop(&mut self) {
+    self.stop();
+  }
+}
+
+fn work(shared: &Shared) {
+  let mut q = shared.queue.lock().unwrap();
+  loop {
+    let (key, job) = match q.ready.pop_front() {
+      Some(next) => next,
+      None if q.stopping && q.busy.is_empty() => return,
+      None => {
+        q = shared.wake.wait(q).unwrap();
+        continue;
+      }
+    };
+    drop(q);
+
+    // a panicking job mustn't take its worker down, or hold its key forever
+    // the panic itself was already printed by the hook
+    if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+      error!("A job on {:?} panicked", key);
+    }
+
+    q = shared.queue.lock().unwrap();
+    if let Some(key) = key {
+      // the next job on this key, if any, is ready now
+      match q.busy.get_mut(&key).and_then(|waiting| waiting.pop_front()) {
+        Some(next) => {
+          q.ready.push_back((Some(key), next));
+          shared.wake.notify_one();
+        }
+        None => {
+          q.busy.remove(&key);
+          if q.stopping && q.busy.is_empty() {
+            shared.wake.notify_all();
+          }
+        }
+      }
+    }
+  }
+}
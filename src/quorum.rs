@@ -0,0 +1,83 @@
+use anyhow::Result;
+
+use super::{Error, ErrorCode, Message, MsgBody, NodeID, Outbox};
+
+// the smallest majority of `n` nodes
+pub fn majority(n: usize) -> usize {
+  n / 2 + 1
+}
+
+// sends `body` from `src` to each of `nodes`, with a fresh `msg_id` each,
+// `expect` is called with the id before it's sent so no reply can be missed
+// returns the ids, in the order of `nodes`
+pub(crate) fn fan_out(
+  outbox: &Outbox,
+  src: &str,
+  nodes: &[NodeID],
+  body: &MsgBody,
+  mut expect: impl FnMut(u64),
+) -> Result<Vec<u64>> {
+  let mut ids = Vec::with_capacity(nodes.len());
+  for n in nodes {
+    let id = outbox.gen_id();
+    let body = MsgBody {
+      msg_id: Some(id),
+      ..body.clone()
+    };
+    expect(id);
+    ids.push(id);
+    outbox.send_from(src, n, body)?;
+  }
+  Ok(ids)
+}
+
+// counts replies to a fanned out request until `need` of them succeeded, or
+// too many failed for that to happen
+// `error` replies and timeouts are failures
+pub(crate) struct Tally {
+  need: usize,
+  pending: usize,
+  oks: Vec<Message>,
+  decided: bool,
+}
+
+impl Tally {
+  pub(crate) fn new(need: usize, asked: usize) -> Self {
+    Tally {
+      need,
+      pending: asked,
+      oks: vec![],
+      decided: false,
+    }
+  }
+
+  // `None` for a timeout
+  pub(crate) fn add(&mut self, reply: Option<Message>) {
+    self.pending = self.pending.saturating_sub(1);
+    if let Some(reply) = reply.filter(|r| Error::from_reply(&r.body).is_none()) {
+      self.oks.push(reply);
+    }
+  }
+
+  // whether `outcome` has something
+  pub(crate) fn ready(&self) -> bool {
+    !self.decided && (self.oks.len() >= self.need || self.oks.len() + self.pending < self.need)
+  }
+
+  // the successful replies once there's a quorum, or the error once there can't
+  // be one, only the first time
+  pub(crate) fn outcome(&mut self) -> Option<std::result::Result<Vec<Message>, Error>> {
+    if !self.ready() {
+      return None;
+    }
+
+    self.decided = true;
+    if self.oks.len() >= self.need {
+      Some(Ok(std::mem::take(&mut self.oks)))
+    } else {
+      let text = format!("{} of {} needed replies", self.oks.len(), self.need);
+      // indefinite, some of those that failed may have applied it
+      Some(Err(Error::new(ErrorCode::Timeout, text)))
+    }
+  }
+}
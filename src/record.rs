@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+  fs::File,
+  io::{BufRead, BufReader, LineWriter, Write},
+  path::Path,
+  sync::Mutex,
+  time::Instant,
+};
+
+use super::Message;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+  In,
+  Out,
+}
+
+// one line of a trace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+  // seconds since the recording started
+  pub t: f64,
+  pub dir: Direction,
+  pub msg: Message,
+}
+
+enum Sink {
+  // the path still needs the node id, lines are kept until `init` gives it
+  Pending(String, Vec<String>),
+  File(LineWriter<File>),
+}
+
+// writes every message in and out of a node to a JSONL file, enabled with
+// `--record <path>` or `MAELSTROM_RECORD`
+// Maelstrom starts every node with the same arguments, `{node}` in the path is
+// replaced with the node's id and `{pid}` with the process id, so that each
+// gets its own file, with `{node}` the file is only created at `init`
+// lines are written as they happen, so a trace survives the node crashing
+pub struct Recorder {
+  started: Instant,
+  sink: Mutex<Sink>,
+}
+
+impl Recorder {
+  pub fn create(path: &str) -> Result<Self> {
+    let path = path.replace("{pid}", &std::process::id().to_string());
+    let sink = if path.contains("{node}") {
+      Sink::Pending(path, vec![])
+    } else {
+      Sink::File(open(&path)?)
+    };
+
+    Ok(Recorder {
+      started: Instant::now(),
+      sink: Mutex::new(sink),
+    })
+  }
+
+  // creates the file once the node knows its id, if the path was waiting on it
+  pub fn set_node(&self, id: &str) -> Result<()> {
+    let mut sink = self.sink.lock().unwrap();
+    if let Sink::Pending(path, lines) = &*sink {
+      let mut file = open(&path.replace("{node}", id))?;
+      for line in lines {
+        writeln!(file, "{}", line)?;
+      }
+      *sink = Sink::File(file);
+    }
+    Ok(())
+  }
+
+  pub fn record(&self, dir: Direction, msg: &Message) -> Result<()> {
+    let r = Record {
+      t: self.started.elapsed().as_secs_f64(),
+      dir,
+      msg: msg.clone(),
+    };
+    let line = serde_json::to_string(&r)?;
+    match &mut *self.sink.lock().unwrap() {
+      Sink::Pending(_, lines) => lines.push(line),
+      Sink::File(file) => writeln!(file, "{}", line)?,
+    }
+    Ok(())
+  }
+}
+
+fn open(path: &str) -> Result<LineWriter<File>> {
+  let file = File::create(path).with_context(|| format!("creating trace {}", path))?;
+  Ok(LineWriter::new(file))
+}
+
+pub fn read_trace(path: impl AsRef<Path>) -> Result<Vec<Record>> {
+  let path = path.as_ref();
+  let file = File::open(path).with_context(|| format!("opening trace {}", path.display()))?;
+  BufReader::new(file)
+    .lines()
+    .enumerate()
+    .filter(|(_, line)| !line.as_ref().is_ok_and(|l| l.trim().is_empty()))
+    .map(|(i, line)| {
+      let line = line?;
+      serde_json::from_str(&line).with_context(|| format!("{}:{}", path.display(), i + 1))
+    })
+    .collect()
+}
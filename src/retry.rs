@@ -0,0 +1,95 @@
+use std::{
+  cmp::Reverse,
+  collections::{BinaryHeap, HashMap},
+  time::{Duration, Instant},
+};
+
+use super::Message;
+
+struct Pending {
+  msg: Message,
+  deadline: Instant,
+  backoff: Duration,
+}
+
+// tracks sent messages until they're acknowledged, and tells when to send them again
+// retries back off exponentially from `initial` up to `max`
+// keyed by `msg_id`, so tracked messages must have one
+pub struct Retransmitter {
+  initial: Duration,
+  max: Duration,
+  pending: HashMap<u64, Pending>,
+  // entries of acked or rescheduled messages are skipped when popped
+  queue: BinaryHeap<Reverse<(Instant, u64)>>,
+}
+
+impl Default for Retransmitter {
+  fn default() -> Self {
+    Retransmitter::new(Duration::from_millis(500), Duration::from_millis(8_000))
+  }
+}
+
+impl Retransmitter {
+  pub fn new(initial: Duration, max: Duration) -> Self {
+    Retransmitter {
+      initial,
+      max,
+      pending: HashMap::new(),
+      queue: BinaryHeap::new(),
+    }
+  }
+
+  // `msg` was just sent, the first retry is due after `initial`
+  pub fn track(&mut self, msg: Message, now: Instant) {
+    let id = msg.body.msg_id.expect("tracked message must have a msg_id");
+    let deadline = now + self.initial;
+
+    self.queue.push(Reverse((deadline, id)));
+    self.pending.insert(
+      id,
+      Pending {
+        msg,
+        deadline,
+        backoff: self.initial,
+      },
+    );
+  }
+
+  // stops retrying the message, returns it if it was still pending
+  pub fn ack(&mut self, in_reply_to: u64) -> Option<Message> {
+    self.pending.remove(&in_reply_to).map(|p| p.msg)
+  }
+
+  // messages due for sending again, rescheduled with a doubled backoff
+  pub fn poll(&mut self, now: Instant) -> Vec<Message> {
+    let mut due = vec![];
+
+    while let Some(Reverse((deadline, id))) = self.queue.peek().cloned() {
+      if deadline > now {
+        break;
+      }
+      self.queue.pop();
+
+      if let Some(p) = self.pending.get_mut(&id) {
+        if p.deadline != deadline {
+          continue;
+        }
+
+        p.backoff = (p.backoff * 2).min(self.max);
+        p.deadline = now + p.backoff;
+        self.queue.push(Reverse((p.deadline, id)));
+        due.push(p.msg.clone());
+      }
+    }
+
+    due
+  }
+
+  pub fn len(&self) -> usize {
+    self.pending.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.pending.is_empty()
+  }
+}
@@ -0,0 +1,111 @@
+use anyhow::Result;
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  hash::Hash,
+};
+
+use super::{config, hash_of, NodeID};
+
+// consistent hashing of keys to nodes, for sharding
+// each node is placed at `vnodes` points of a ring of hashes, a key belongs
+// to the nodes at the first points from its hash on, clockwise
+// a node joining or leaving only moves the keys next to its own points,
+// about 1/n of them, and the virtual nodes spread them evenly
+// every node must use the same key type, as keys are placed by their hash
+// requests for keys a node doesn't own can be handed over with `Node::forward`
+#[derive(Debug, Clone)]
+pub struct HashRing {
+  vnodes: usize,
+  points: BTreeMap<u64, NodeID>,
+  nodes: BTreeSet<NodeID>,
+}
+
+impl HashRing {
+  pub fn new(nodes: impl IntoIterator<Item = NodeID>, vnodes: usize) -> Self {
+    let mut ring = HashRing {
+      vnodes: vnodes.max(1),
+      points: BTreeMap::new(),
+      nodes: BTreeSet::new(),
+    };
+    for n in nodes {
+      ring.add(&n);
+    }
+    ring
+  }
+
+  // `nodes` is typically `init`'s `node_ids`, reads `--vnodes`, 64 by default,
+  // see `config::lookup`
+  pub fn from_env(nodes: impl IntoIterator<Item = NodeID>) -> Result<Self> {
+    Ok(HashRing::new(nodes, config::parse_or("vnodes", 64)?))
+  }
+
+  // false if it's there already
+  pub fn add(&mut self, node: &str) -> bool {
+    if !self.nodes.insert(node.to_owned()) {
+      return false;
+    }
+
+    for i in 0..self.vnodes {
+      // on the off chance two points collide, the first node keeps it
+      self
+        .points
+        .entry(hash_of(&(node, i)))
+        .or_insert_with(|| node.to_owned());
+    }
+    true
+  }
+
+  // false if it wasn't there
+  pub fn remove(&mut self, node: &str) -> bool {
+    if !self.nodes.remove(node) {
+      return false;
+    }
+
+    self.points.retain(|_, n| n != node);
+    true
+  }
+
+  // the node `key` belongs to, `None` if the ring is empty
+  pub fn owner<K: Hash + ?Sized>(&self, key: &K) -> Option<NodeID> {
+    self.owners(key, 1).pop()
+  }
+
+  // the `n` distinct nodes holding replicas of `key`, the first is its owner,
+  // all the nodes if there are no more than `n`
+  pub fn owners<K: Hash + ?Sized>(&self, key: &K, n: usize) -> Vec<NodeID> {
+    let n = n.min(self.nodes.len());
+    let h = hash_of(key);
+
+    let mut owners: Vec<NodeID> = Vec::with_capacity(n);
+    for node in self
+      .points
+      .range(h..)
+      .chain(self.points.range(..h))
+      .map(|(_, node)| node)
+    {
+      if owners.len() == n {
+        break;
+      }
+      if !owners.contains(node) {
+        owners.push(node.clone());
+      }
+    }
+    owners
+  }
+
+  pub fn contains(&self, node: &str) -> bool {
+    self.nodes.contains(node)
+  }
+
+  pub fn nodes(&self) -> impl Iterator<Item = &NodeID> {
+    self.nodes.iter()
+  }
+
+  pub fn len(&self) -> usize {
+    self.nodes.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.nodes.is_empty()
+  }
+}
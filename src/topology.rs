@@ -0,0 +1,141 @@
+use anyhow::{anyhow, Result};
+use std::{
+  collections::{HashMap, HashSet, VecDeque},
+  str::FromStr,
+};
+
+use super::NodeID;
+
+pub type Topology = HashMap<NodeID, Vec<NodeID>>;
+
+// gossip graph a node uses, nodes are laid out in the order of `init`'s `node_ids`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overlay {
+  // the `topology` Maelstrom sends
+  Given,
+  // BFS tree of the given topology rooted at the first node, a star without one
+  SpanningTree,
+  // k-ary tree rooted at the first node
+  Tree(usize),
+  // square-ish grid, linked to the nodes above, below, left and right
+  Grid,
+  // the first node linked to all others, which also form a ring
+  HubRing,
+}
+
+impl FromStr for Overlay {
+  type Err = anyhow::Error;
+
+  // `given`, `spanning-tree`, `tree` (binary) or `tree:<k>`, `grid`, `hub-ring`
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "given" => Ok(Overlay::Given),
+      "spanning-tree" => Ok(Overlay::SpanningTree),
+      "tree" => Ok(Overlay::Tree(2)),
+      "grid" => Ok(Overlay::Grid),
+      "hub-ring" => Ok(Overlay::HubRing),
+      _ => match s.strip_prefix("tree:").map(str::parse) {
+        Some(Ok(k)) if k > 0 => Ok(Overlay::Tree(k)),
+        _ => Err(anyhow!(
+          "expected given, spanning-tree, tree, tree:<k>, grid or hub-ring"
+        )),
+      },
+    }
+  }
+}
+
+impl Overlay {
+  // whether the overlay is built from Maelstrom's `topology`, instead of only `node_ids`
+  pub fn uses_topology(self) -> bool {
+    matches!(self, Overlay::Given | Overlay::SpanningTree)
+  }
+
+  pub fn neighbors(self, node: &str, nodes: &[NodeID], given: Option<&Topology>) -> Vec<NodeID> {
+    let n = nodes.len();
+    let i = match nodes.iter().position(|x| x == node) {
+      Some(i) => i,
+      None => return vec![],
+    };
+
+    let idx: Vec<usize> = match self {
+      Overlay::Given => return given.and_then(|t| t.get(node)).cloned().unwrap_or_default(),
+      Overlay::SpanningTree => return spanning_tree(node, nodes, given),
+      Overlay::Tree(k) => {
+        // any wider is the same star, and `k * i + k` can't overflow
+        let k = k.clamp(1, n);
+        let parent = if i > 0 { Some((i - 1) / k) } else { None };
+        parent
+          .into_iter()
+          .chain((k * i + 1..=k * i + k).filter(|c| *c < n))
+          .collect()
+      }
+      Overlay::Grid => {
+        let cols = (n as f64).sqrt().ceil() as usize;
+        let mut idx = vec![];
+        if i % cols > 0 {
+          idx.push(i - 1);
+        }
+        if i % cols + 1 < cols && i + 1 < n {
+          idx.push(i + 1);
+        }
+        if i >= cols {
+          idx.push(i - cols);
+        }
+        if i + cols < n {
+          idx.push(i + cols);
+        }
+        idx
+      }
+      Overlay::HubRing => {
+        if i == 0 {
+          (1..n).collect()
+        } else {
+          // the ring is over nodes 1..n
+          let ring = n - 1;
+          let pos = i - 1;
+          let mut idx = vec![0, (pos + 1) % ring + 1, (pos + ring - 1) % ring + 1];
+          idx.sort_unstable();
+          idx.dedup();
+          idx.retain(|x| *x != i);
+          idx
+        }
+      }
+    };
+
+    idx.into_iter().map(|x| nodes[x].clone()).collect()
+  }
+}
+
+fn spanning_tree(node: &str, nodes: &[NodeID], given: Option<&Topology>) -> Vec<NodeID> {
+  let root = match nodes.first() {
+    Some(root) => root,
+    None => return vec![],
+  };
+  let edges = |x: &NodeID| -> Vec<NodeID> {
+    match given {
+      Some(t) => t.get(x).cloned().unwrap_or_default(),
+      None => nodes.iter().filter(|y| *y != x).cloned().collect(),
+    }
+  };
+
+  let mut seen: HashSet<NodeID> = HashSet::new();
+  let mut queue = VecDeque::new();
+  let mut neighbors = vec![];
+  seen.insert(root.clone());
+  queue.push_back(root.clone());
+  while let Some(x) = queue.pop_front() {
+    for y in edges(&x) {
+      if seen.insert(y.clone()) {
+        // tree edge x - y
+        if x == node {
+          neighbors.push(y.clone());
+        } else if y == node {
+          neighbors.push(x.clone());
+        }
+        queue.push_back(y);
+      }
+    }
+  }
+
+  neighbors
+}
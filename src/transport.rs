@@ -0,0 +1,328 @@
+use anyhow::{anyhow, Context, Result};
+use std::{
+  collections::{HashMap, HashSet},
+  io::{self, BufRead, BufReader, Write},
+  net::{TcpListener, TcpStream, ToSocketAddrs},
+  sync::{
+    mpsc::{self, Receiver, Sender},
+    Arc, Mutex,
+  },
+  thread,
+  time::{Duration, Instant},
+};
+
+use super::{config, error, info, warn, Message, MsgBody, NodeID};
+
+// how a node gets and sends its messages
+pub trait Transport {
+  // inbound messages, the channel disconnecting means the input is closed
+  fn incoming(&mut self) -> Result<Receiver<Message>>;
+  // the sending half, it's moved to the writer thread
+  fn outgoing(&mut self) -> Result<Box<dyn Outgoing>>;
+}
+
+pub trait Outgoing: Send {
+  // an error drops `msg`, `BrokenPipe` means nothing can be sent anymore
+  fn send(&mut self, msg: &Message) -> io::Result<()>;
+}
+
+// newline separated JSON on stdin and stdout, what Maelstrom speaks
+pub struct Stdio;
+
+impl Transport for Stdio {
+  fn incoming(&mut self) -> Result<Receiver<Message>> {
+    let (tx, rx) = mpsc::channel();
+    // dropping `tx` when stdin is done disconnects the channel, that's our EOF
+    thread::spawn(move || read_lines(io::stdin().lock(), "stdin", |msg| tx.send(msg).is_ok()));
+    Ok(rx)
+  }
+
+  fn outgoing(&mut self) -> Result<Box<dyn Outgoing>> {
+    Ok(Box::new(StdoutOut))
+  }
+}
+
+struct StdoutOut;
+
+impl Outgoing for StdoutOut {
+  fn send(&mut self, msg: &Message) -> io::Result<()> {
+    let line = serde_json::to_string(msg)?;
+    let mut out = io::stdout().lock();
+    writeln!(out, "{}", line)?;
+    out.flush()
+  }
+}
+
+// messages passed over channels in the same process, for tests and simulations
+pub struct Memory {
+  rx: Option<Receiver<Message>>,
+  tx: Sender<Message>,
+}
+
+impl Memory {
+  // the transport, where to send the node's input and where its output comes out
+  // dropping the input sender closes the node's input
+  pub fn channel() -> (Memory, Sender<Message>, Receiver<Message>) {
+    let (in_tx, in_rx) = mpsc::channel();
+    let (out_tx, out_rx) = mpsc::channel();
+    let m = Memory {
+      rx: Some(in_rx),
+      tx: out_tx,
+    };
+    (m, in_tx, out_rx)
+  }
+}
+
+impl Transport for Memory {
+  fn incoming(&mut self) -> Result<Receiver<Message>> {
+    self
+      .rx
+      .take()
+      .ok_or_else(|| anyhow!("memory transport already started"))
+  }
+
+  fn outgoing(&mut self) -> Result<Box<dyn Outgoing>> {
+    Ok(Box::new(MemoryOut(self.tx.clone())))
+  }
+}
+
+struct MemoryOut(Sender<Message>);
+
+impl Outgoing for MemoryOut {
+  fn send(&mut self, msg: &Message) -> io::Result<()> {
+    self.0.send(msg.clone()).map_err(|_| io::ErrorKind::BrokenPipe.into())
+  }
+}
+
+// the source of the `init` a TCP node gives itself, replies to it go nowhere
+const TCP_INIT_SRC: &str = "tcp";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+// how long messages to a peer that couldn't be reached are dropped without
+// trying again, doubling up to the max while it stays unreachable
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+// newline separated JSON over TCP, to run workloads as a real cluster
+// each node listens on its own address in `peers` and initializes itself with
+// all of `peers` as the cluster, clients connect to any node and get replies
+// on the same connection
+pub struct Tcp {
+  id: NodeID,
+  peers: HashMap<NodeID, String>,
+  conns: Arc<Mutex<HashMap<NodeID, TcpStream>>>,
+  inbox: Option<Sender<Message>>,
+}
+
+impl Tcp {
+  pub fn new(id: &str, peers: HashMap<NodeID, String>) -> Self {
+    Tcp {
+      id: id.to_owned(),
+      peers,
+      conns: Default::default(),
+      inbox: None,
+    }
+  }
+
+  // `--node-id n1 --peers n1=127.0.0.1:7001,n2=127.0.0.1:7002,..`
+  pub fn from_config() -> Result<Self> {
+    let id = config::lookup("node-id").ok_or_else(|| anyhow!("tcp transport needs a node-id"))?;
+    let peers = config::lookup("peers")
+      .ok_or_else(|| anyhow!("tcp transport needs peers"))?
+      .split(',')
+      .map(|p| match p.split_once('=') {
+        Some((node, addr)) => Ok((node.trim().to_owned(), addr.trim().to_owned())),
+        None => Err(anyhow!("expected <node>=<addr> in peers, got {:?}", p)),
+      })
+      .collect::<Result<_>>()?;
+    Ok(Tcp::new(&id, peers))
+  }
+}
+
+impl Transport for Tcp {
+  fn incoming(&mut self) -> Result<Receiver<Message>> {
+    let addr = self
+      .peers
+      .get(&self.id)
+      .ok_or_else(|| anyhow!("{} isn't in peers", self.id))?;
+    let listener = TcpListener::bind(addr).with_context(|| format!("listening on {}", addr))?;
+    info!("Listening on {}", addr);
+
+    let (tx, rx) = mpsc::channel();
+    let mut node_ids: Vec<NodeID> = self.peers.keys().cloned().collect();
+    node_ids.sort();
+    let init = MsgBody {
+      typ: "init".to_owned(),
+      msg_id: Some(0),
+      node_id: Some(self.id.clone()),
+      node_ids: Some(node_ids),
+      ..Default::default()
+    };
+    let _ = tx.send(Message {
+      src: TCP_INIT_SRC.to_owned(),
+      dest: self.id.clone(),
+      body: init,
+    });
+
+    let (conns, inbox) = (self.conns.clone(), tx.clone());
+    thread::spawn(move || {
+      for stream in listener.incoming() {
+        match stream {
+          Ok(stream) => read_conn(stream, inbox.clone(), conns.clone()),
+          Err(e) => warn!("Failed accepting a connection: {}", e),
+        }
+      }
+    });
+
+    self.inbox = Some(tx);
+    Ok(rx)
+  }
+
+  fn outgoing(&mut self) -> Result<Box<dyn Outgoing>> {
+    Ok(Box::new(TcpOut {
+      peers: self.peers.clone(),
+      conns: self.conns.clone(),
+      down: HashMap::new(),
+      inbox: self
+        .inbox
+        .clone()
+        .ok_or_else(|| anyhow!("tcp transport isn't listening"))?,
+    }))
+  }
+}
+
+struct TcpOut {
+  peers: HashMap<NodeID, String>,
+  conns: Arc<Mutex<HashMap<NodeID, TcpStream>>>,
+  // unreachable peers, when to try again and the backoff so far
+  down: HashMap<NodeID, (Instant, Duration)>,
+  inbox: Sender<Message>,
+}
+
+impl TcpOut {
+  // called without holding `conns`, a slow connect holds up only this send
+  fn connect(&mut self, dest: &str) -> io::Result<TcpStream> {
+    if let Some((retry_at, _)) = self.down.get(dest) {
+      if Instant::now() < *retry_at {
+        return Err(io::ErrorKind::NotConnected.into());
+      }
+    }
+
+    let addr = self.peers.get(dest).ok_or(io::ErrorKind::NotFound)?;
+    let addr = addr.to_socket_addrs()?.next().ok_or(io::ErrorKind::AddrNotAvailable)?;
+    match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+      Ok(stream) => {
+        self.down.remove(dest);
+        Ok(stream)
+      }
+      Err(e) => {
+        let backoff = match self.down.get(dest) {
+          Some((_, b)) => (*b * 2).min(MAX_BACKOFF),
+          None => MIN_BACKOFF,
+        };
+        self.down.insert(dest.to_owned(), (Instant::now() + backoff, backoff));
+        Err(e)
+      }
+    }
+  }
+}
+
+impl Outgoing for TcpOut {
+  fn send(&mut self, msg: &Message) -> io::Result<()> {
+    if msg.dest == TCP_INIT_SRC {
+      return Ok(());
+    }
+    let line = serde_json::to_string(msg)? + "\n";
+
+    let connected = self.conns.lock().unwrap().contains_key(&msg.dest);
+    if !connected {
+      let stream = self.connect(&msg.dest)?;
+      // they may answer on this connection
+      read_conn(stream.try_clone()?, self.inbox.clone(), self.conns.clone());
+      // unless they connected to us in the meantime
+      self.conns.lock().unwrap().entry(msg.dest.clone()).or_insert(stream);
+    }
+
+    let mut conns = self.conns.lock().unwrap();
+    let stream = conns.get_mut(&msg.dest).ok_or(io::ErrorKind::NotConnected)?;
+    stream.write_all(line.as_bytes()).map_err(|e| {
+      conns.remove(&msg.dest);
+      // one peer going away doesn't close the node's output
+      match e.kind() {
+        io::ErrorKind::BrokenPipe => io::ErrorKind::ConnectionReset.into(),
+        _ => e,
+      }
+    })
+  }
+}
+
+// reads messages off a connection, replies to their sources go back on it
+fn read_conn(stream: TcpStream, inbox: Sender<Message>, conns: Arc<Mutex<HashMap<NodeID, TcpStream>>>) {
+  thread::spawn(move || {
+    let reader = match stream.try_clone() {
+      Ok(s) => BufReader::new(s),
+      Err(e) => {
+        warn!("Failed reading a connection: {}", e);
+        return;
+      }
+    };
+
+    // each source is registered once, it's usually the only one on the connection
+    let mut sources = HashSet::new();
+    read_lines(reader, "connection", |msg| {
+      if !sources.contains(&msg.src) {
+        match stream.try_clone() {
+          Ok(s) => {
+            conns.lock().unwrap().entry(msg.src.clone()).or_insert(s);
+            sources.insert(msg.src.clone());
+          }
+          Err(e) => warn!("Failed registering {}: {}", msg.src, e),
+        }
+      }
+      inbox.send(msg).is_ok()
+    });
+  });
+}
+
+// parses lines for `deliver` until the input ends or it returns false
+// malformed lines are skipped
+fn read_lines(input: impl BufRead, what: &str, mut deliver: impl FnMut(Message) -> bool) {
+  for line in input.lines() {
+    let line = match line {
+      Ok(line) if line.trim().is_empty() => continue,
+      Ok(line) => line,
+      Err(e) => {
+        error!("Failed reading {}: {}", what, e);
+        break;
+      }
+    };
+
+    match serde_json::from_str(&line) {
+      Ok(msg) => {
+        if !deliver(msg) {
+          break;
+        }
+      }
+      Err(e) => error!("Malformed message {:?}: {}", line, e),
+    }
+  }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+  fn incoming(&mut self) -> Result<Receiver<Message>> {
+    (**self).incoming()
+  }
+
+  fn outgoing(&mut self) -> Result<Box<dyn Outgoing>> {
+    (**self).outgoing()
+  }
+}
+
+// the transport `--transport`/`MAELSTROM_TRANSPORT` picks, `stdio` or `tcp`
+pub(crate) fn from_config() -> Result<Box<dyn Transport>> {
+  match config::lookup("transport").as_deref() {
+    None | Some("stdio") => Ok(Box::new(Stdio)),
+    Some("tcp") => Ok(Box::new(Tcp::from_config()?)),
+    Some(t) => Err(anyhow!("invalid transport {:?}: expected stdio or tcp", t)),
+  }
+}
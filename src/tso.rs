@@ -0,0 +1,53 @@
+use anyhow::{anyhow, Result};
+use std::sync::{
+  atomic::{AtomicU64, Ordering},
+  Arc,
+};
+
+use super::{AsyncContext, MsgBody};
+
+// hands out timestamps, each greater than any handed out before it
+#[allow(async_fn_in_trait)]
+pub trait TimestampOracle {
+  async fn ts(&self) -> Result<u64>;
+}
+
+// a client for Maelstrom's `lin-tso` service
+#[derive(Clone)]
+pub struct Tso {
+  ctx: AsyncContext,
+}
+
+impl Tso {
+  pub fn new(ctx: &AsyncContext) -> Self {
+    Tso { ctx: ctx.clone() }
+  }
+}
+
+impl TimestampOracle for Tso {
+  async fn ts(&self) -> Result<u64> {
+    let ts = MsgBody {
+      typ: "ts".to_owned(),
+      ..Default::default()
+    };
+
+    let resp = self.ctx.call("lin-tso", ts).await?;
+    resp
+      .body
+      .ts
+      .ok_or_else(|| anyhow!("lin-tso replied {} without a ts", resp.body.typ))
+  }
+}
+
+// a stand-in for `lin-tso` without Maelstrom, e.g. in tests
+// only monotonic among its clones, not across processes
+#[derive(Debug, Clone, Default)]
+pub struct LocalTso {
+  next: Arc<AtomicU64>,
+}
+
+impl TimestampOracle for LocalTso {
+  async fn ts(&self) -> Result<u64> {
+    Ok(self.next.fetch_add(1, Ordering::SeqCst))
+  }
+}
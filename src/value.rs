@@ -0,0 +1,77 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::{
+  cmp::Ordering,
+  hash::{Hash, Hasher},
+};
+
+// a JSON value that can go in sets, compared and hashed by its canonical
+// serialization: `serde_json` keeps object keys sorted, so equal objects
+// serialize the same regardless of key order
+// numbers are compared as written, `1` and `1.0` are different values
+#[derive(Debug, Clone)]
+pub struct CanonicalValue {
+  value: Value,
+  canonical: String,
+}
+
+impl CanonicalValue {
+  pub fn value(&self) -> &Value {
+    &self.value
+  }
+
+  pub fn into_value(self) -> Value {
+    self.value
+  }
+}
+
+impl From<Value> for CanonicalValue {
+  fn from(value: Value) -> Self {
+    let canonical = value.to_string();
+    CanonicalValue { value, canonical }
+  }
+}
+
+impl From<CanonicalValue> for Value {
+  fn from(cv: CanonicalValue) -> Self {
+    cv.value
+  }
+}
+
+impl PartialEq for CanonicalValue {
+  fn eq(&self, other: &Self) -> bool {
+    self.canonical == other.canonical
+  }
+}
+
+impl Eq for CanonicalValue {}
+
+impl Hash for CanonicalValue {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.canonical.hash(state);
+  }
+}
+
+impl PartialOrd for CanonicalValue {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for CanonicalValue {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.canonical.cmp(&other.canonical)
+  }
+}
+
+impl Serialize for CanonicalValue {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    self.value.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for CanonicalValue {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    Value::deserialize(deserializer).map(CanonicalValue::from)
+  }
+}
@@ -0,0 +1,66 @@
+use anyhow::Result;
+use std::time::Duration;
+
+use maelstrom::*;
+
+mod common;
+use common::*;
+
+// asks `svc` for its `echo`, and replies with it
+struct Ask;
+
+impl AsyncHandler for Ask {
+  async fn handle(&self, ctx: &AsyncContext, msg: Message) -> Result<()> {
+    let ping = MsgBody {
+      echo: msg.body.echo.clone(),
+      ..body("ping")
+    };
+    let pong = ctx.call("svc", ping).await?;
+    ctx.sleep(Duration::from_millis(10)).await;
+
+    let r = MsgBody {
+      echo: pong.body.echo,
+      ..body("ask_ok")
+    };
+    ctx.reply(&msg, r)
+  }
+}
+
+#[test]
+fn rpcs_interleave() {
+  let n = TestNode::start(&["n1"], || Async::new(Ask));
+
+  for (id, echo) in [(2, "a"), (3, "b")] {
+    let ask = MsgBody {
+      echo: Some(echo.into()),
+      ..request("ask", id)
+    };
+    n.send(msg("c1", ask));
+  }
+
+  // both are waiting on svc at once, answer them in reverse
+  let mut pings: Vec<Message> = (0..2).map(|_| n.recv()).collect();
+  assert!(pings.iter().all(|p| p.dest == "svc" && p.body.typ == "ping"));
+  pings.reverse();
+  for p in pings {
+    let pong = MsgBody {
+      echo: p.body.echo.clone(),
+      ..body("pong")
+    };
+    n.send(reply_to(&p, pong));
+  }
+
+  let mut replies: Vec<(u64, String)> = (0..2)
+    .map(|_| n.recv())
+    .map(|r| {
+      (
+        r.body.in_reply_to.unwrap(),
+        r.body.echo.unwrap().as_str().unwrap().to_owned(),
+      )
+    })
+    .collect();
+  replies.sort();
+  assert_eq!(replies, vec![(2, "a".to_owned()), (3, "b".to_owned())]);
+
+  assert!(n.stop().is_empty());
+}
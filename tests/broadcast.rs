@@ -0,0 +1,143 @@
+use serde_json::{json, Value};
+use std::{collections::HashSet, thread, time::Duration};
+
+use maelstrom::*;
+
+mod common;
+use common::*;
+
+const NODES: [&str; 3] = ["n1", "n2", "n3"];
+
+// a line, n1 - n2 - n3, so values for n3 go through n2
+fn line(args: &[&str]) -> Cluster {
+  let line = json!({"n1": ["n2"], "n2": ["n1", "n3"], "n3": ["n2"]});
+  with_topology(line, args)
+}
+
+fn with_topology(topology: Value, args: &[&str]) -> Cluster {
+  let mut c = Cluster::start(env!("CARGO_BIN_EXE_broadcast"), &NODES, args);
+  for id in NODES {
+    let topology = MsgBody {
+      topology: Some(serde_json::from_value(topology.clone()).unwrap()),
+      ..body("topology")
+    };
+    c.request(id, topology);
+  }
+  c
+}
+
+impl Cluster {
+  fn broadcast(&mut self, dest: &str, value: u64) {
+    let b = MsgBody {
+      message: Some(value.into()),
+      ..body("broadcast")
+    };
+    assert_eq!(self.request(dest, b).body.typ, "broadcast_ok");
+  }
+
+  fn read(&mut self, dest: &str) -> HashSet<u64> {
+    let r = self.request(dest, body("read"));
+    r.body
+      .messages
+      .unwrap_or_default()
+      .iter()
+      .map(|v| v.as_u64().unwrap())
+      .collect()
+  }
+
+  // waits for every node to have read `values`
+  fn converges(&mut self, values: &[u64]) {
+    let want: HashSet<u64> = values.iter().copied().collect();
+    for id in NODES {
+      eventually(&format!("{} doesn't have {:?}", id, want), || self.read(id) == want);
+    }
+  }
+}
+
+// only the retries can fix it, with anti-entropy off
+fn retransmits_dropped_batches(args: &[&str]) {
+  let mut c = line(&[args, &["--batch-window", "10", "--sync-interval", "60000"]].concat());
+  {
+    let mut net = c.net.lock().unwrap();
+    net.drop_next.push(("n1".into(), "n2".into(), "gossip".into()));
+    net.drop_next.push(("n2".into(), "n3".into(), "gossip".into()));
+  }
+
+  c.broadcast("n1", 1);
+  c.broadcast("n1", 2);
+  c.converges(&[1, 2]);
+  assert!(c.net.lock().unwrap().drop_next.is_empty());
+}
+
+fn catches_up_after_partition(args: &[&str]) {
+  let mut c = line(&[args, &["--batch-window", "10", "--sync-interval", "100"]].concat());
+  c.net.lock().unwrap().cut.insert("n3".into());
+
+  c.broadcast("n1", 1);
+  c.broadcast("n3", 2);
+  c.broadcast("n2", 3);
+  // each side only has its own
+  thread::sleep(Duration::from_millis(300));
+  assert_eq!(c.read("n1"), [1, 3].into());
+  assert_eq!(c.read("n3"), [2].into());
+
+  c.net.lock().unwrap().cut.clear();
+  c.converges(&[1, 2, 3]);
+}
+
+#[test]
+fn dropped_batches_are_retransmitted() {
+  retransmits_dropped_batches(&[]);
+}
+
+#[test]
+fn partitioned_nodes_catch_up() {
+  catches_up_after_partition(&[]);
+}
+
+#[test]
+fn plumtree_retransmits_dropped_batches() {
+  retransmits_dropped_batches(&["--strategy", "plumtree"]);
+}
+
+#[test]
+fn plumtree_catches_up_after_partition() {
+  catches_up_after_partition(&["--strategy", "plumtree"]);
+}
+
+#[test]
+fn plumtree_converges_without_anti_entropy() {
+  let mesh = json!({"n1": ["n2", "n3"], "n2": ["n1", "n3"], "n3": ["n1", "n2"]});
+  let args = [
+    "--strategy",
+    "plumtree",
+    "--batch-window",
+    "10",
+    "--sync-interval",
+    "60000",
+  ];
+  let mut c = with_topology(mesh, &args);
+
+  // one at a time, so later values go along the pruned tree
+  let values: Vec<u64> = (1..=12).collect();
+  for (i, id) in NODES.iter().cycle().take(values.len()).enumerate() {
+    c.broadcast(id, values[i]);
+    c.converges(&values[..=i]);
+  }
+
+  // the mesh was pruned down to a tree, lazy links only announce
+  let net = c.net.lock().unwrap();
+  assert!(net.delivered("prune").count() > 0);
+  assert!(net.delivered("ihave").count() > 0);
+}
+
+#[test]
+fn stray_errors_are_ignored() {
+  let mut c = line(&[]);
+  // an error that answers nothing it sent
+  let line = r#"{"src":"n2","dest":"n1","body":{"type":"error","code":11,"in_reply_to":99}}"#;
+  c.send_line("n1", line);
+
+  c.broadcast("n1", 1);
+  assert_eq!(c.read("n1"), [1].into());
+}
@@ -0,0 +1,96 @@
+use std::cmp::Ordering;
+
+use maelstrom::*;
+
+#[test]
+fn lamport() {
+  let (mut a, mut b) = (LamportClock::new(), LamportClock::new());
+  let sent = a.tick();
+  a.tick();
+  b.tick();
+  assert_eq!(b.observe(sent), 2);
+  assert_eq!(b.observe(a.time()), 3);
+  assert!(a < b);
+}
+
+#[test]
+fn vector() {
+  let (mut a, mut b) = (VectorClock::new(), VectorClock::new());
+  assert_eq!(a, b);
+
+  a.tick("n1");
+  assert!(b < a);
+  b.merge(&a);
+  b.tick("n2");
+  assert!(a < b);
+  assert_eq!(b.get("n1"), 1);
+
+  a.tick("n1");
+  assert!(a.concurrent(&b));
+  assert_eq!(a.partial_cmp(&b), None);
+
+  a.merge(&b);
+  assert!(b < a);
+  assert_eq!((a.get("n1"), a.get("n2"), a.get("n3")), (2, 1, 0));
+}
+
+#[test]
+fn merge_and_compare_counters() {
+  let ours = [("n1", 3u64), ("n2", 1)]
+    .iter()
+    .map(|(n, c)| (n.to_string(), *c))
+    .collect();
+  let mut theirs = [("n2", 4u64), ("n3", 0)]
+    .iter()
+    .map(|(n, c)| (n.to_string(), *c))
+    .collect();
+  assert_eq!(compare_entries(&ours, &theirs), None);
+
+  merge_max(&mut theirs, &ours);
+  assert_eq!(theirs.get("n1"), Some(&3));
+  assert_eq!(theirs.get("n2"), Some(&4));
+  assert_eq!(compare_entries(&ours, &theirs), Some(Ordering::Less));
+  assert_eq!(compare_entries(&theirs, &theirs), Some(Ordering::Equal));
+}
+
+#[test]
+fn hybrid() {
+  let mut a = HybridClock::new();
+  let t1 = a.now_at(100);
+  assert_eq!((t1.wall, t1.logical), (100, 0));
+  // the physical clock went back, it still moves forward
+  let t2 = a.now_at(90);
+  assert_eq!((t2.wall, t2.logical), (100, 1));
+
+  // a message from a node ahead of us
+  let remote = HlcTimestamp { wall: 150, logical: 4 };
+  let t3 = a.observe_at(remote, 120);
+  assert_eq!((t3.wall, t3.logical), (150, 5));
+  assert!(t1 < t2 && t2 < t3 && remote < t3);
+
+  let t4 = a.observe_at(HlcTimestamp { wall: 10, logical: 9 }, 200);
+  assert_eq!((t4.wall, t4.logical), (200, 0));
+  assert!(a.now() > t4);
+}
+
+#[test]
+fn piggybacked() {
+  let mut vclock = VectorClock::new();
+  vclock.tick("n1");
+  let body = MsgBody {
+    typ: "write".to_owned(),
+    lamport: Some(LamportClock::new().tick()),
+    vclock: Some(vclock.clone()),
+    hlc: Some(HybridClock::new().now_at(7)),
+    ..Default::default()
+  };
+
+  let json = serde_json::to_string(&body).unwrap();
+  assert!(json.contains(r#""vclock":{"n1":1}"#), "{}", json);
+  assert!(json.contains(r#""hlc":{"wall":7,"logical":0}"#), "{}", json);
+
+  let back: MsgBody = serde_json::from_str(&json).unwrap();
+  assert_eq!(back.lamport, Some(1));
+  assert_eq!(back.vclock, Some(vclock));
+  assert_eq!(back.hlc, body.hlc);
+}
@@ -0,0 +1,334 @@
+// running a node over the `Memory` transport, or one of the binaries, each
+// test binary uses a part
+#![allow(dead_code)]
+
+use anyhow::Result;
+use std::{
+  collections::{HashMap, HashSet},
+  io::{BufRead, BufReader, Write},
+  process::{Child, ChildStdin, Command, ExitStatus, Stdio},
+  sync::{
+    mpsc::{self, Receiver, Sender},
+    Arc, Mutex,
+  },
+  thread::{self, JoinHandle},
+  time::{Duration, Instant},
+};
+
+use maelstrom::*;
+
+// the node under test
+pub const ID: &str = "n1";
+
+// how long to wait for something the node should send
+pub const WAIT: Duration = Duration::from_secs(5);
+
+// `init` for a binary, on its stdin
+pub const INIT: &str =
+  r#"{"src":"c1","dest":"n1","body":{"type":"init","msg_id":1,"node_id":"n1","node_ids":["n1","n2"]}}"#;
+
+// a node on its own thread, fed and read by the test
+pub struct TestNode {
+  pub input: Sender<Message>,
+  pub output: Receiver<Message>,
+  node: JoinHandle<Result<()>>,
+}
+
+impl TestNode {
+  // not initialized yet, `state` is built on the node's thread as `Async` isn't `Send`
+  pub fn spawn<S: Handler + 'static>(state: impl FnOnce() -> S + Send + 'static) -> Self {
+    let (transport, input, output) = Memory::channel();
+    let node = thread::spawn(move || Node::run_with(state(), transport));
+    TestNode { input, output, node }
+  }
+
+  // initialized as `n1`, the first of `node_ids`
+  pub fn start<S: Handler + 'static>(node_ids: &[&str], state: impl FnOnce() -> S + Send + 'static) -> Self {
+    let n = TestNode::spawn(state);
+    n.send(init(node_ids));
+    assert_eq!(n.recv().body.typ, "init_ok");
+    n
+  }
+
+  pub fn send(&self, msg: Message) {
+    self.input.send(msg).unwrap();
+  }
+
+  // the next message the node sends
+  pub fn recv(&self) -> Message {
+    self.output.recv_timeout(WAIT).expect("nothing sent")
+  }
+
+  // closes the input, and returns what the node sent until it shut down
+  pub fn stop(self) -> Vec<Message> {
+    drop(self.input);
+    self.node.join().unwrap().unwrap();
+    self.output.iter().collect()
+  }
+}
+
+pub fn body(typ: &str) -> MsgBody {
+  MsgBody {
+    typ: typ.to_owned(),
+    ..Default::default()
+  }
+}
+
+pub fn request(typ: &str, msg_id: u64) -> MsgBody {
+  MsgBody {
+    msg_id: Some(msg_id),
+    ..body(typ)
+  }
+}
+
+// to the node under test
+pub fn msg(src: &str, body: MsgBody) -> Message {
+  Message {
+    src: src.to_owned(),
+    dest: ID.to_owned(),
+    body,
+  }
+}
+
+pub fn reply_to(req: &Message, body: MsgBody) -> Message {
+  Message {
+    src: req.dest.clone(),
+    dest: req.src.clone(),
+    body: MsgBody {
+      in_reply_to: req.body.msg_id,
+      ..body
+    },
+  }
+}
+
+// from `c1`, as `msg_id` 1
+pub fn init(node_ids: &[&str]) -> Message {
+  let mut init = request("init", 1);
+  init.node_id = Some(ID.to_owned());
+  init.node_ids = Some(node_ids.iter().map(|n| n.to_string()).collect());
+  msg("c1", init)
+}
+
+// feeds `input` to the binary, closes its stdin and waits for it to exit on its own
+pub fn run_to_eof(bin: &str, input: &[&str]) -> Vec<String> {
+  run_args_to_eof(bin, &[], input)
+}
+
+// like `run_to_eof`, started with `args`
+pub fn run_args_to_eof(bin: &str, args: &[&str], input: &[&str]) -> Vec<String> {
+  let mut child = Command::new(bin)
+    .args(args)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn()
+    .unwrap();
+
+  let mut stdin = child.stdin.take().unwrap();
+  for line in input {
+    writeln!(stdin, "{}", line).unwrap();
+  }
+  drop(stdin);
+
+  wait_for_exit(&mut child, bin);
+  BufReader::new(child.stdout.take().unwrap())
+    .lines()
+    .map(|l| l.unwrap())
+    .collect()
+}
+
+// what the binary logs given `args` and `input`, once it's exited
+pub fn stderr_of(bin: &str, args: &[&str], input: &[&str]) -> String {
+  let mut child = Command::new(bin)
+    .args(args)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::null())
+    .stderr(Stdio::piped())
+    .spawn()
+    .unwrap();
+
+  let mut stdin = child.stdin.take().unwrap();
+  for line in input {
+    writeln!(stdin, "{}", line).unwrap();
+  }
+  drop(stdin);
+
+  String::from_utf8(child.wait_with_output().unwrap().stderr).unwrap()
+}
+
+// how the binary exits when started with `args`, and no input
+pub fn exit_status(bin: &str, args: &[&str]) -> ExitStatus {
+  Command::new(bin)
+    .args(args)
+    .stdin(Stdio::null())
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .status()
+    .unwrap()
+}
+
+pub fn wait_for_exit(child: &mut Child, bin: &str) {
+  let started = Instant::now();
+  let status = loop {
+    if let Some(status) = child.try_wait().unwrap() {
+      break status;
+    }
+    if started.elapsed() > WAIT {
+      child.kill().unwrap();
+      panic!("{} didn't exit after EOF", bin);
+    }
+    thread::sleep(Duration::from_millis(10));
+  };
+  assert!(status.success(), "{} exited with {}", bin, status);
+}
+
+// polls `done` until it's true, or fails the test after WAIT
+pub fn eventually(what: &str, mut done: impl FnMut() -> bool) {
+  let deadline = Instant::now() + WAIT;
+  while !done() {
+    assert!(Instant::now() < deadline, "{}", what);
+    thread::sleep(Duration::from_millis(50));
+  }
+}
+
+// what the network between a cluster's nodes lets through
+#[derive(Default)]
+pub struct Net {
+  // nodes that can't reach, or be reached by, the others
+  pub cut: HashSet<String>,
+  // `(src, dest, type)` of messages to drop once
+  pub drop_next: Vec<(String, String, String)>,
+  // what went through, in order
+  pub delivered: Vec<Message>,
+}
+
+impl Net {
+  fn delivers(&mut self, msg: &Message) -> bool {
+    if self.cut.contains(&msg.src) != self.cut.contains(&msg.dest) {
+      return false;
+    }
+    let key = (msg.src.clone(), msg.dest.clone(), msg.body.typ.clone());
+    match self.drop_next.iter().position(|k| *k == key) {
+      Some(i) => {
+        self.drop_next.remove(i);
+        false
+      }
+      None => {
+        self.delivered.push(msg.clone());
+        true
+      }
+    }
+  }
+
+  pub fn delivered(&self, typ: &str) -> impl Iterator<Item = &Message> {
+    let typ = typ.to_owned();
+    self.delivered.iter().filter(move |m| m.body.typ == typ)
+  }
+}
+
+// one of the binaries per node, each node's output routed to the others'
+// input through `net`, and what's sent to clients back to the test
+pub struct Cluster {
+  pub net: Arc<Mutex<Net>>,
+  inputs: Arc<Mutex<HashMap<String, ChildStdin>>>,
+  replies: Receiver<Message>,
+  children: Vec<Child>,
+  next_id: u64,
+}
+
+impl Cluster {
+  // initialized, started with `args`
+  pub fn start(bin: &str, nodes: &[&str], args: &[&str]) -> Self {
+    let net = Arc::new(Mutex::new(Net::default()));
+    let inputs = Arc::new(Mutex::new(HashMap::new()));
+    let (to_client, replies) = mpsc::channel();
+    let mut children = vec![];
+
+    for id in nodes {
+      let mut child = Command::new(bin)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+      inputs
+        .lock()
+        .unwrap()
+        .insert(id.to_string(), child.stdin.take().unwrap());
+
+      let stdout = BufReader::new(child.stdout.take().unwrap());
+      let (net, inputs, to_client) = (net.clone(), inputs.clone(), to_client.clone());
+      let nodes: Vec<String> = nodes.iter().map(|n| n.to_string()).collect();
+      thread::spawn(move || {
+        for line in stdout.lines() {
+          let Ok(line) = line else { break };
+          let msg: Message = serde_json::from_str(&line).unwrap();
+          if !nodes.contains(&msg.dest) {
+            let _ = to_client.send(msg);
+          } else if net.lock().unwrap().delivers(&msg) {
+            // it may be shutting down
+            let _ = writeln!(inputs.lock().unwrap().get_mut(&msg.dest).unwrap(), "{}", line);
+          }
+        }
+      });
+      children.push(child);
+    }
+
+    let mut cluster = Cluster {
+      net,
+      inputs,
+      replies,
+      children,
+      next_id: 0,
+    };
+    for id in nodes {
+      let init = MsgBody {
+        node_id: Some(id.to_string()),
+        node_ids: Some(nodes.iter().map(|n| n.to_string()).collect()),
+        ..body("init")
+      };
+      assert_eq!(cluster.request(id, init).body.typ, "init_ok");
+    }
+    cluster
+  }
+
+  // a raw line on the node's input
+  pub fn send_line(&self, dest: &str, line: &str) {
+    writeln!(self.inputs.lock().unwrap().get_mut(dest).unwrap(), "{}", line).unwrap();
+  }
+
+  // sends `body` from a client, and waits for the reply
+  pub fn request(&mut self, dest: &str, body: MsgBody) -> Message {
+    self.next_id += 1;
+    let id = self.next_id;
+    let msg = Message {
+      src: "c1".to_owned(),
+      dest: dest.to_owned(),
+      body: MsgBody {
+        msg_id: Some(id),
+        ..body
+      },
+    };
+    self.send_line(dest, &serde_json::to_string(&msg).unwrap());
+
+    let deadline = Instant::now() + WAIT;
+    loop {
+      let left = deadline.saturating_duration_since(Instant::now());
+      let r = self.replies.recv_timeout(left).expect("no reply");
+      if r.body.in_reply_to == Some(id) {
+        return r;
+      }
+    }
+  }
+}
+
+impl Drop for Cluster {
+  fn drop(&mut self) {
+    self.inputs.lock().unwrap().clear();
+    for child in &mut self.children {
+      let _ = child.kill();
+      let _ = child.wait();
+    }
+  }
+}
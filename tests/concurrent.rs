@@ -0,0 +1,128 @@
+use anyhow::Result;
+use std::{collections::HashMap, env, thread, time::Duration};
+
+use maelstrom::*;
+
+mod common;
+use common::*;
+
+// replies to `echo`, asks `svc` on `ask`, and takes a while over `append`
+struct Service;
+
+impl SyncHandler for Service {
+  fn handle(&self, ctx: &Context, msg: Message) -> Result<()> {
+    match msg.body.typ.as_str() {
+      "echo" => {
+        let r = MsgBody {
+          echo: msg.body.echo.clone(),
+          ..body("echo_ok")
+        };
+        ctx.reply(&msg, r)
+      }
+      "ask" => match ctx.rpc("svc", body("ping")) {
+        Ok(pong) => {
+          let r = MsgBody {
+            value: pong.body.msg_id.map(Into::into),
+            ..body("ask_ok")
+          };
+          ctx.reply(&msg, r)
+        }
+        Err(e) => ctx.reply(&msg, e.downcast::<Error>()?.into()),
+      },
+      "append" => {
+        // give later messages a chance to overtake, if they could
+        thread::sleep(Duration::from_micros(300 * (msg.body.msg_id.unwrap() % 4)));
+        ctx.reply(&msg, body("append_ok"))
+      }
+      _ => Ok(()),
+    }
+  }
+}
+
+fn start(ordering: OrderBy) -> TestNode {
+  TestNode::start(&["n1"], move || Concurrent::new(Service, 4, ordering))
+}
+
+#[test]
+fn replies_from_workers() {
+  let n = start(OrderBy::Source);
+
+  let echo = MsgBody {
+    echo: Some("hi".into()),
+    ..request("echo", 2)
+  };
+  n.send(msg("c1", echo));
+  let r = n.recv();
+  assert_eq!((r.src.as_str(), r.dest.as_str()), ("n1", "c1"));
+  assert_eq!(r.body.in_reply_to, Some(2));
+  assert_eq!(r.body.echo, Some("hi".into()));
+
+  assert!(n.stop().is_empty());
+}
+
+#[test]
+fn workers_block_on_rpcs() {
+  let n = start(OrderBy::Unordered);
+
+  // both are waiting on svc at once, answer them in reverse
+  n.send(msg("c1", request("ask", 2)));
+  n.send(msg("c2", request("ask", 2)));
+  let pings = [n.recv(), n.recv()];
+  assert!(pings.iter().all(|p| p.dest == "svc" && p.body.typ == "ping"));
+  for (p, id) in pings.iter().rev().zip([100, 101]) {
+    n.send(reply_to(p, request("pong", id)));
+  }
+
+  let mut replies: Vec<(String, u64)> = (0..2)
+    .map(|_| n.recv())
+    .map(|r| (r.dest, r.body.value.unwrap().as_u64().unwrap()))
+    .collect();
+  replies.sort();
+  assert_eq!(replies.len(), 2);
+  assert!(replies.iter().all(|(_, v)| *v == 100 || *v == 101));
+  assert_ne!(replies[0].1, replies[1].1);
+
+  assert!(n.stop().is_empty());
+}
+
+#[test]
+fn same_key_is_handled_in_order() {
+  let n = start(OrderBy::Key);
+
+  let keys = ["a", "b", "c"];
+  for id in 2..62 {
+    let mut append = request("append", id);
+    append.key = Some(keys[id as usize % keys.len()].into());
+    // from different clients, so per source ordering wouldn't do
+    n.send(msg(&format!("c{}", id % 5), append));
+  }
+
+  let mut order: HashMap<usize, Vec<u64>> = HashMap::new();
+  for _ in 2..62 {
+    let id = n.recv().body.in_reply_to.unwrap();
+    order.entry(id as usize % keys.len()).or_default().push(id);
+  }
+  for ids in order.values() {
+    assert!(ids.windows(2).all(|w| w[0] < w[1]), "out of order: {:?}", ids);
+  }
+
+  assert!(n.stop().is_empty());
+}
+
+#[test]
+fn late_replies_are_dropped() {
+  env::set_var("MAELSTROM_RPC_TIMEOUT", "50");
+  let n = TestNode::start(&["n1"], || Concurrent::from_config(Service).unwrap());
+
+  n.send(msg("c1", request("ask", 2)));
+  let ping = n.recv();
+  let r = n.recv();
+  assert_eq!(r.body.in_reply_to, Some(2));
+  assert_eq!(r.body.code, Some(ErrorCode::Timeout as u64));
+
+  // the worker gave up on it, nobody handles it
+  n.send(reply_to(&ping, request("pong", 100)));
+  n.send(msg("c1", request("echo", 3)));
+  assert_eq!(n.recv().body.in_reply_to, Some(3));
+  assert!(n.stop().is_empty());
+}
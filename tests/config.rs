@@ -0,0 +1,62 @@
+mod common;
+use common::*;
+
+const ADD: &str = r#"{"src":"c1","dest":"n1","body":{"type":"add","msg_id":2,"delta":3}}"#;
+
+// whether g-counter pushed its state to n2 on the add, with gossip rounds
+// too far apart to have done it
+fn pushes(args: &[&str]) -> bool {
+  let mut args = args.to_vec();
+  args.extend(["--gossip-interval", "60000"]);
+  run_args_to_eof(env!("CARGO_BIN_EXE_g-counter"), &args, &[INIT, ADD])
+    .iter()
+    .any(|l| l.contains(r#""replicate""#) && l.contains(r#""dest":"n2""#))
+}
+
+#[test]
+fn bare_flags_are_true() {
+  let bin = env!("CARGO_BIN_EXE_g-counter");
+
+  assert!(!pushes(&[]));
+  // followed by another flag, or last
+  assert!(pushes(&["--push-on-write"]));
+  assert!(pushes(&["--gossip-mode", "push", "--push-on-write"]));
+  assert!(pushes(&["--push-on-write", "--gossip-mode", "push"]));
+
+  assert!(pushes(&["--push-on-write", "1"]));
+  assert!(pushes(&["--push-on-write=true"]));
+  assert!(!pushes(&["--push-on-write", "0"]));
+  assert!(!pushes(&["--push-on-write", "false"]));
+
+  assert!(!exit_status(bin, &["--push-on-write", "yes"]).success());
+  // a value is still needed where a flag isn't a switch
+  assert!(!exit_status(bin, &["--gossip-fanout", "--push-on-write"]).success());
+}
+
+#[test]
+fn log_settings_are_checked() {
+  let log = |args: &[&str]| stderr_of(env!("CARGO_BIN_EXE_echo"), args, &[INIT]);
+  // the init coming in, as it was read
+  let traced = |log: &str| log.contains(r#"in {"src":"c1""#);
+
+  assert!(!traced(&log(&[])));
+  assert!(traced(&log(&["--trace-messages"])));
+  assert!(traced(&log(&["--trace-messages", "--log-level", "info"])));
+  assert!(traced(&log(&["--trace-messages", "true"])));
+  assert!(!traced(&log(&["--trace-messages", "0"])));
+
+  let bad = log(&["--trace-messages", "yes"]);
+  assert!(bad.contains("invalid trace-messages"), "{}", bad);
+  assert!(!traced(&bad));
+
+  let json = log(&["--log-format", "json"]);
+  assert!(
+    json
+      .lines()
+      .all(|l| serde_json::from_str::<serde_json::Value>(l).is_ok()),
+    "{}",
+    json
+  );
+  let bad = log(&["--log-format", "xml"]);
+  assert!(bad.contains("invalid log-format"), "{}", bad);
+}
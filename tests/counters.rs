@@ -0,0 +1,162 @@
+use maelstrom::*;
+
+mod common;
+use common::*;
+
+// the replies to the requests after `init`, by `msg_id`
+fn replies(bin: &str, requests: &[&str]) -> Vec<MsgBody> {
+  let lines: Vec<String> = requests
+    .iter()
+    .enumerate()
+    .map(|(i, body)| format!(r#"{{"src":"c1","dest":"n1","body":{{"msg_id":{},{}}}}}"#, i + 2, body))
+    .collect();
+  let mut input = vec![INIT];
+  input.extend(lines.iter().map(String::as_str));
+
+  let mut out: Vec<MsgBody> = run_to_eof(bin, &input)
+    .iter()
+    .map(|l| serde_json::from_str::<Message>(l).unwrap())
+    .filter(|m| m.dest == "c1" && m.body.typ != "init_ok")
+    .map(|m| m.body)
+    .collect();
+  out.sort_by_key(|b| b.in_reply_to);
+  assert_eq!(out.len(), requests.len());
+  out
+}
+
+fn code(b: &MsgBody) -> Option<ErrorCode> {
+  Error::from_reply(b).map(|e| e.code)
+}
+
+#[test]
+fn g_counter_rejects_bad_deltas() {
+  let r = replies(
+    env!("CARGO_BIN_EXE_g-counter"),
+    &[
+      r#""type":"add","delta":-1"#,
+      r#""type":"add","delta":"one""#,
+      r#""type":"add""#,
+      r#""type":"add","delta":2"#,
+      r#""type":"read""#,
+    ],
+  );
+
+  for b in &r[..3] {
+    assert_eq!(code(b), Some(ErrorCode::MalformedRequest));
+  }
+  assert_eq!(r[3].typ, "add_ok");
+  assert_eq!(r[4].value, Some(2.into()));
+}
+
+#[cfg(not(feature = "bigint"))]
+#[test]
+fn pn_counter_keeps_its_total_in_range() {
+  let max = format!(r#""type":"add","delta":{}"#, i64::MAX);
+  let r = replies(
+    env!("CARGO_BIN_EXE_pn-counter"),
+    &[
+      &max,
+      r#""type":"add","delta":1"#,
+      r#""type":"add","delta":-5"#,
+      r#""type":"read""#,
+    ],
+  );
+
+  assert_eq!(r[0].typ, "add_ok");
+  // it'd fit the counter, but not the total
+  assert_eq!(code(&r[1]), Some(ErrorCode::Abort));
+  assert_eq!(r[2].typ, "add_ok");
+  assert_eq!(r[3].value, Some((i64::MAX - 5).into()));
+}
+
+fn converges(bin: &str, mode: &str) {
+  let nodes = ["n1", "n2"];
+  let mut c = Cluster::start(bin, &nodes, &["--gossip-mode", mode, "--gossip-interval", "50"]);
+  for (id, delta) in [("n1", 3), ("n2", 4), ("n1", 5)] {
+    let add = MsgBody {
+      delta: Some(delta.into()),
+      ..body("add")
+    };
+    assert_eq!(c.request(id, add).body.typ, "add_ok");
+  }
+
+  for id in nodes {
+    eventually(&format!("{} didn't converge in {} mode", bin, mode), || {
+      c.request(id, body("read")).body.value == Some(12.into())
+    });
+  }
+}
+
+#[test]
+fn counters_converge_in_every_mode() {
+  for bin in [env!("CARGO_BIN_EXE_g-counter"), env!("CARGO_BIN_EXE_pn-counter")] {
+    for mode in ["push", "pull", "push-pull"] {
+      converges(bin, mode);
+    }
+  }
+}
+
+#[cfg(feature = "bigint")]
+#[test]
+fn pn_counter_takes_any_integer() {
+  // 2^70
+  let big = r#""type":"add","delta":1180591620717411303424"#;
+  let r = replies(
+    env!("CARGO_BIN_EXE_pn-counter"),
+    &[
+      big,
+      r#""type":"add","delta":-1"#,
+      r#""type":"add","delta":1.5"#,
+      r#""type":"read""#,
+    ],
+  );
+
+  assert_eq!(r[0].typ, "add_ok");
+  assert_eq!(r[1].typ, "add_ok");
+  assert_eq!(code(&r[2]), Some(ErrorCode::MalformedRequest));
+  assert_eq!(r[3].value.as_ref().unwrap().to_string(), "1180591620717411303423");
+}
+
+// adds on both sides of a partition that only fit the total on their own
+#[test]
+fn pn_counter_merges_past_i64() {
+  let nodes = ["n1", "n2"];
+  let mut c = Cluster::start(env!("CARGO_BIN_EXE_pn-counter"), &nodes, &["--gossip-interval", "50"]);
+  let add = |c: &mut Cluster, id: &str, delta: i64| {
+    let add = MsgBody {
+      delta: Some(delta.into()),
+      ..body("add")
+    };
+    assert_eq!(c.request(id, add).body.typ, "add_ok");
+  };
+
+  c.net.lock().unwrap().cut.insert("n2".into());
+  add(&mut c, "n1", i64::MAX);
+  add(&mut c, "n2", 2);
+  c.net.lock().unwrap().cut.clear();
+
+  let read = |c: &mut Cluster, id: &str| c.request(id, body("read")).body;
+  #[cfg(not(feature = "bigint"))]
+  {
+    for id in nodes {
+      eventually("the total didn't overflow", || {
+        code(&read(&mut c, id)) == Some(ErrorCode::Abort)
+      });
+    }
+    // decrements are taken while it's out of range
+    add(&mut c, "n1", -1);
+    assert_eq!(code(&read(&mut c, "n1")), Some(ErrorCode::Abort));
+    add(&mut c, "n1", -1);
+    for id in nodes {
+      eventually("the total isn't back", || {
+        read(&mut c, id).value == Some(i64::MAX.into())
+      });
+    }
+  }
+  #[cfg(feature = "bigint")]
+  for id in nodes {
+    eventually("the total isn't past i64", || {
+      read(&mut c, id).value.map(|v| v.to_string()) == Some("9223372036854775809".to_owned())
+    });
+  }
+}
@@ -0,0 +1,110 @@
+use anyhow::{anyhow, Result};
+use std::{cell::Cell, env};
+
+use maelstrom::*;
+
+mod common;
+use common::*;
+
+// dedup is off by default
+fn dedup() {
+  env::set_var("MAELSTROM_DEDUP_CAPACITY", "100");
+}
+
+// counts everything it handles, not idempotent
+// replies to `add` and leaves the rest unanswered
+#[derive(Default)]
+struct Counter(u64);
+
+impl Handler for Counter {
+  fn handle(&mut self, node: &mut Node<Self>, msg: Message) -> Result<()> {
+    self.0 += 1;
+    match msg.body.typ.as_str() {
+      "add" => {
+        let r = MsgBody {
+          value: Some(self.0.into()),
+          ..body("add_ok")
+        };
+        node.reply(&msg, r)
+      }
+      _ => Ok(()),
+    }
+  }
+}
+
+#[test]
+fn retries_get_the_first_reply() {
+  dedup();
+  let n = TestNode::spawn(Counter::default);
+  n.send(init(&["n1"]));
+  for id in [2, 2, 3] {
+    n.send(msg("c1", request("add", id)));
+  }
+
+  let adds: Vec<Message> = n.stop().into_iter().filter(|m| m.body.typ == "add_ok").collect();
+  assert_eq!(adds.len(), 3);
+  // the retry of 2 is answered from the table, without counting again
+  assert_eq!(adds[0].body.msg_id, adds[1].body.msg_id);
+  assert_eq!(adds[1].body.value, Some(1.into()));
+  assert_eq!(adds[2].body.in_reply_to, Some(3));
+  assert_eq!(adds[2].body.value, Some(2.into()));
+}
+
+#[test]
+fn unanswered_requests_are_handled_again() {
+  dedup();
+  let n = TestNode::start(&["n1"], Counter::default);
+  for (typ, id) in [("skip", 2), ("skip", 2), ("add", 3)] {
+    n.send(msg("c1", request(typ, id)));
+  }
+
+  // the retry wasn't dropped as still in flight
+  assert_eq!(n.recv().body.value, Some(3.into()));
+  assert!(n.stop().is_empty());
+}
+
+// asks `svc` on `ask`, fails `fail`, counting both
+#[derive(Default)]
+struct Asker(Cell<u64>);
+
+impl AsyncHandler for Asker {
+  async fn handle(&self, ctx: &AsyncContext, msg: Message) -> Result<()> {
+    self.0.set(self.0.get() + 1);
+    match msg.body.typ.as_str() {
+      "ask" => {
+        ctx.call("svc", body("ping")).await?;
+        let r = MsgBody {
+          value: Some(self.0.get().into()),
+          ..body("ask_ok")
+        };
+        ctx.reply(&msg, r)
+      }
+      _ => Err(anyhow!("failed")),
+    }
+  }
+}
+
+#[test]
+fn async_requests_are_in_flight_until_done() {
+  dedup();
+  let n = TestNode::start(&["n1"], || Async::new(Asker::default()));
+
+  n.send(msg("c1", request("fail", 2)));
+  n.send(msg("c1", request("ask", 3)));
+  let ping = n.recv();
+  // still waiting on svc, the retry is dropped
+  n.send(msg("c1", request("ask", 3)));
+  n.send(reply_to(&ping, body("pong")));
+  let r = n.recv();
+  assert_eq!(r.body.in_reply_to, Some(3));
+  assert_eq!(r.body.value, Some(2.into()));
+
+  // the failure was settled, so it's handled again, the answered one is replayed
+  n.send(msg("c1", request("fail", 2)));
+  n.send(msg("c1", request("ask", 3)));
+  assert_eq!(n.recv().body.value, Some(2.into()));
+  n.send(msg("c1", request("ask", 4)));
+  assert_eq!(n.recv().dest, "svc");
+
+  assert!(n.stop().is_empty());
+}
@@ -0,0 +1,109 @@
+use anyhow::Result;
+use std::{
+  sync::mpsc::{self, Sender},
+  thread,
+  time::{Duration, Instant},
+};
+
+use maelstrom::*;
+
+mod common;
+use common::*;
+
+const BEAT: Duration = Duration::from_millis(100);
+
+#[test]
+fn suspects_silent_peers() {
+  let mut fd = FailureDetector::new(8.0, BEAT);
+  let start = Instant::now();
+  let at = |ms: u64| start + Duration::from_millis(ms);
+
+  for t in (0..=1_000).step_by(100) {
+    assert!(!fd.heartbeat("n2", at(t)));
+    fd.heartbeat("n3", at(t));
+  }
+  assert_eq!(fd.phi("n4", at(1_000)), None);
+
+  // n3 goes quiet, a late heartbeat isn't a failure, a long silence is
+  fd.heartbeat("n2", at(1_100));
+  assert!(fd.poll(at(1_150)).is_empty());
+  assert!(fd.phi("n3", at(1_150)).unwrap() < 1.0);
+
+  for t in (1_200..=1_800).step_by(100) {
+    fd.heartbeat("n2", at(t));
+  }
+  assert_eq!(fd.poll(at(1_800)), vec!["n3".to_owned()]);
+  assert!(fd.is_suspected("n3"));
+  assert!(!fd.is_suspected("n2"));
+  // only reported once
+  fd.heartbeat("n2", at(1_900));
+  assert!(fd.poll(at(1_900)).is_empty());
+
+  assert!(fd.heartbeat("n3", at(2_000)));
+  assert!(!fd.is_suspected("n3"));
+}
+
+#[test]
+fn only_heartbeats_are_timed() {
+  let mut fd = FailureDetector::new(8.0, BEAT);
+  let start = Instant::now();
+  let at = |ms: u64| start + Duration::from_millis(ms);
+
+  // chatty between heartbeats
+  for t in (0..=1_000).step_by(10) {
+    if t % 100 == 0 {
+      fd.heartbeat("n2", at(t));
+    } else {
+      fd.seen("n2", at(t));
+    }
+  }
+
+  // a pause of a few heartbeats is judged against them, not the chatter
+  assert!(fd.poll(at(1_550)).is_empty());
+  assert_eq!(fd.poll(at(2_000)), vec!["n2".to_owned()]);
+
+  // anything brings it back, and counts as its last sign of life
+  assert!(fd.seen("n2", at(2_100)));
+  assert!(fd.phi("n2", at(2_150)).unwrap() < 1.0);
+}
+
+// reports suspicion changes to the test
+struct Watch(Sender<(String, bool)>);
+
+impl Handler for Watch {
+  fn init(&mut self, node: &mut Node<Self>) -> Result<()> {
+    node.detect_failures(FailureDetector::new(3.0, BEAT));
+    node.on_suspicion(|s, node, peer, suspected| {
+      assert_eq!(node.is_suspected(peer), suspected);
+      s.0.send((peer.to_owned(), suspected)).unwrap();
+      Ok(())
+    });
+    Ok(())
+  }
+
+  fn handle(&mut self, _: &mut Node<Self>, _: Message) -> Result<()> {
+    Ok(())
+  }
+}
+
+#[test]
+fn node_watches_peers() {
+  let (changes, rx) = mpsc::channel();
+  let TestNode { input, output, .. } = TestNode::start(&["n1", "n2", "n3"], move || Watch(changes));
+
+  // n2 keeps beating, n3 never says anything
+  let hb = input.clone();
+  thread::spawn(move || {
+    for m in output {
+      if m.body.typ == "heartbeat" && m.dest == "n2" && hb.send(msg("n2", body("heartbeat"))).is_err() {
+        return;
+      }
+    }
+  });
+
+  assert_eq!(rx.recv_timeout(WAIT).unwrap(), ("n3".to_owned(), true));
+
+  // any message brings it back
+  input.send(msg("n3", body("hello"))).unwrap();
+  assert_eq!(rx.recv_timeout(WAIT).unwrap(), ("n3".to_owned(), false));
+}
@@ -0,0 +1,47 @@
+use std::collections::HashSet;
+
+use maelstrom::*;
+
+#[test]
+fn order_doesnt_matter() {
+  let xs: Vec<u64> = (0..500).collect();
+  let mut reversed = xs.clone();
+  reversed.reverse();
+  let set: HashSet<u64> = xs.iter().copied().collect();
+
+  let d = Digest::of(&xs);
+  assert_eq!(d, Digest::of(&reversed));
+  assert_eq!(d, Digest::of(&set));
+  assert_eq!(d.count, 500);
+  assert_eq!(d.buckets.len(), BUCKETS);
+}
+
+#[test]
+fn summaries_match_equal_sets() {
+  let xs: Vec<u64> = (0..100).collect();
+  let d = Digest::of(&xs);
+  assert!(d.summary().buckets.is_empty());
+  assert!(d.summary().matches(&d));
+  assert!(d.matches(&Digest::of(xs.iter().rev())));
+
+  assert!(!d.matches(&Digest::of(&xs[1..])));
+  // same count, different elements
+  assert!(!d.matches(&Digest::of(&(1..=100).collect::<Vec<u64>>())));
+}
+
+#[test]
+fn diff_is_only_the_differing_buckets() {
+  let ours: Vec<u64> = (0..1_000).collect();
+  assert_eq!(Digest::of(&ours).diff(&Digest::of(&ours)), Vec::<usize>::new());
+
+  let mut theirs = ours.clone();
+  theirs.push(5_000);
+  assert_eq!(Digest::of(&ours).diff(&Digest::of(&theirs)), vec![bucket_of(&5_000u64)]);
+
+  // one missing and one extra element
+  theirs.retain(|x| *x != 7);
+  let mut want = vec![bucket_of(&5_000u64), bucket_of(&7u64)];
+  want.sort_unstable();
+  want.dedup();
+  assert_eq!(Digest::of(&theirs).diff(&Digest::of(&ours)), want);
+}
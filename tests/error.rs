@@ -0,0 +1,18 @@
+use anyhow::anyhow;
+
+use maelstrom::*;
+
+#[test]
+fn any_error_can_be_replied() {
+  let ours = Error::new(ErrorCode::PreconditionFailed, "stale");
+  let e = Error::from_any(anyhow::Error::from(ours).context("cas"));
+  assert_eq!(e.code, ErrorCode::PreconditionFailed);
+
+  // e.g. a trace that couldn't be written, whether it took effect is unknown
+  let e = Error::from_any(anyhow!("disk full").context("recording"));
+  assert_eq!(e.code, ErrorCode::Crash);
+  assert_eq!(e.text, "recording: disk full");
+
+  let reply = MsgBody::from(e);
+  assert_eq!(Error::from_reply(&reply).map(|e| e.code), Some(ErrorCode::Crash));
+}
@@ -0,0 +1,54 @@
+use anyhow::Result;
+use std::time::Duration;
+
+use maelstrom::*;
+
+mod common;
+use common::*;
+
+// hands everything to n2
+struct Proxy;
+
+impl Handler for Proxy {
+  fn handle(&mut self, node: &mut Node<Self>, msg: Message) -> Result<()> {
+    node.forward(&msg, "n2", Duration::from_millis(50))
+  }
+}
+
+#[test]
+fn relays_reply_or_times_out() {
+  let n = TestNode::start(&["n1", "n2"], || Proxy);
+
+  let mut read = request("read", 7);
+  read.key = Some("x".into());
+  n.send(msg("c1", read));
+
+  let fwd = n.recv();
+  assert_eq!((fwd.src.as_str(), fwd.dest.as_str()), ("n1", "n2"));
+  assert_eq!(fwd.body.typ, "read");
+  assert_eq!(fwd.body.key, Some("x".into()));
+  assert_ne!(fwd.body.msg_id, Some(7));
+
+  let read_ok = MsgBody {
+    msg_id: Some(100),
+    value: Some(3.into()),
+    ..body("read_ok")
+  };
+  n.send(reply_to(&fwd, read_ok));
+
+  let r = n.recv();
+  assert_eq!((r.src.as_str(), r.dest.as_str()), ("n1", "c1"));
+  assert_eq!(r.body.typ, "read_ok");
+  assert_eq!(r.body.in_reply_to, Some(7));
+  assert_eq!(r.body.value, Some(3.into()));
+
+  // n2 doesn't answer this one
+  n.send(msg("c1", request("read", 8)));
+  assert_eq!(n.recv().dest, "n2");
+  let r = n.recv();
+  assert_eq!(r.dest, "c1");
+  assert_eq!(r.body.in_reply_to, Some(8));
+  assert_eq!(r.body.code, Some(ErrorCode::TemporarilyUnavailable as u64));
+
+  assert!(n.stop().is_empty());
+}
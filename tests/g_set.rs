@@ -0,0 +1,90 @@
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+use maelstrom::*;
+
+mod common;
+use common::*;
+
+const NODES: [&str; 2] = ["n1", "n2"];
+
+impl Cluster {
+  fn add(&mut self, dest: &str, element: &Value) {
+    let add = MsgBody {
+      element: Some(element.clone()),
+      ..body("add")
+    };
+    assert_eq!(self.request(dest, add).body.typ, "add_ok");
+  }
+
+  fn read(&mut self, dest: &str) -> HashSet<CanonicalValue> {
+    let r = self.request(dest, body("read"));
+    match r.body.value {
+      Some(Value::Array(xs)) => xs.into_iter().map(CanonicalValue::from).collect(),
+      v => panic!("expected a set, got {:?}", v),
+    }
+  }
+}
+
+// both nodes have a hundred elements in common and one of their own,
+// only the buckets of those two should be transferred
+fn syncs_only_differing_buckets(mode: &str) {
+  let bin = env!("CARGO_BIN_EXE_g-set");
+  let mut c = Cluster::start(bin, &NODES, &["--gossip-mode", mode, "--gossip-interval", "100"]);
+  c.net.lock().unwrap().cut.insert("n2".into());
+
+  let mut all: Vec<Value> = (0..100).map(|x| json!(x)).collect();
+  for x in &all {
+    c.add("n1", x);
+    c.add("n2", x);
+  }
+  let (ours, theirs) = (json!("n1's"), json!({"of": "n2"}));
+  c.add("n1", &ours);
+  c.add("n2", &theirs);
+  assert_eq!(c.read("n1").len(), 101);
+  c.net.lock().unwrap().cut.clear();
+
+  all.extend([ours.clone(), theirs.clone()]);
+  let want: HashSet<CanonicalValue> = all.into_iter().map(CanonicalValue::from).collect();
+  for id in NODES {
+    eventually(&format!("{} didn't converge in {} mode", id, mode), || {
+      c.read(id) == want
+    });
+  }
+
+  let differing: HashSet<usize> = [ours, theirs]
+    .iter()
+    .map(|x| bucket_of(&CanonicalValue::from(x.clone())))
+    .collect();
+  let net = c.net.lock().unwrap();
+  assert!(net.delivered("sync_elements").count() > 0);
+  for m in net.delivered("sync_elements") {
+    assert!(
+      m.body.buckets.iter().flatten().all(|b| differing.contains(b)),
+      "{:?}",
+      m
+    );
+  }
+  for m in net.delivered("sync_elements").chain(net.delivered("replicate")) {
+    assert!(
+      m.body.set.iter().flatten().all(|x| differing.contains(&bucket_of(x))),
+      "{:?}",
+      m
+    );
+  }
+}
+
+#[test]
+fn push() {
+  syncs_only_differing_buckets("push");
+}
+
+#[test]
+fn pull() {
+  syncs_only_differing_buckets("pull");
+}
+
+#[test]
+fn push_pull() {
+  syncs_only_differing_buckets("push-pull");
+}
@@ -0,0 +1,183 @@
+use anyhow::Result;
+use std::{
+  cell::Cell,
+  rc::Rc,
+  thread,
+  time::{Duration, Instant},
+};
+
+use maelstrom::*;
+
+mod common;
+use common::*;
+
+// timers and RPCs, driven by the requests' types, `value` is a number of ms
+#[derive(Default)]
+struct Timers {
+  ticks: u64,
+  interval: Option<TimerId>,
+}
+
+fn ms(msg: &Message) -> Duration {
+  Duration::from_millis(msg.body.value.as_ref().and_then(|v| v.as_u64()).unwrap_or_default())
+}
+
+fn count(typ: &str, n: u64) -> MsgBody {
+  MsgBody {
+    value: Some(n.into()),
+    ..body(typ)
+  }
+}
+
+impl Handler for Timers {
+  fn handle(&mut self, node: &mut Node<Self>, msg: Message) -> Result<()> {
+    match msg.body.typ.as_str() {
+      "after" => {
+        node.set_timeout(ms(&msg), move |_, node| node.reply(&msg, body("after_ok")));
+        Ok(())
+      }
+      "every" => {
+        self.interval = Some(node.set_interval(ms(&msg), |s, _| {
+          s.ticks += 1;
+          Ok(())
+        }));
+        node.reply(&msg, body("every_ok"))
+      }
+      "count" => node.reply(&msg, count("count_ok", self.ticks)),
+      "stop" => {
+        node.clear_timer(self.interval.take().unwrap());
+        node.reply(&msg, count("stop_ok", self.ticks))
+      }
+      // ticks three times, clearing itself on the last
+      "thrice" => {
+        let mut n = 0;
+        let id = Rc::new(Cell::new(None));
+        let own = id.clone();
+        id.set(Some(node.set_interval(ms(&msg), move |_, node| {
+          n += 1;
+          if n == 3 {
+            node.clear_timer(own.get().unwrap());
+          }
+          node.reply(&msg, count("tick", n))
+        })));
+        Ok(())
+      }
+      "ask" => node.rpc("svc", body("ping"), move |_, node, pong| {
+        node.reply(&msg, count("ask_ok", pong.body.msg_id.unwrap()))
+      }),
+      "ask_within" => {
+        let timeout = ms(&msg);
+        node.rpc_or_timeout("svc", body("ping"), timeout, move |_, node, pong| match pong {
+          Some(pong) => node.reply(&msg, count("ask_ok", pong.body.msg_id.unwrap())),
+          None => node.reply(&msg, MsgBody::error(ErrorCode::Timeout, "svc didn't answer")),
+        })
+      }
+      _ => Ok(()),
+    }
+  }
+}
+
+fn timed(typ: &str, msg_id: u64, ms: u64) -> Message {
+  let body = MsgBody {
+    value: Some(ms.into()),
+    ..request(typ, msg_id)
+  };
+  msg("c1", body)
+}
+
+fn pong(ping: &Message, msg_id: u64) -> Message {
+  reply_to(ping, request("pong", msg_id))
+}
+
+#[test]
+fn timeout_fires_once() {
+  let n = TestNode::start(&["n1"], Timers::default);
+
+  let start = Instant::now();
+  n.send(timed("after", 2, 20));
+  let r = n.recv();
+  assert_eq!(r.body.typ, "after_ok");
+  assert_eq!(r.body.in_reply_to, Some(2));
+  assert!(start.elapsed() >= Duration::from_millis(20));
+
+  thread::sleep(Duration::from_millis(50));
+  assert!(n.stop().is_empty());
+}
+
+#[test]
+fn interval_clears_itself() {
+  let n = TestNode::start(&["n1"], Timers::default);
+
+  n.send(timed("thrice", 2, 5));
+  let ticks: Vec<u64> = (0..3).map(|_| n.recv().body.value.unwrap().as_u64().unwrap()).collect();
+  assert_eq!(ticks, vec![1, 2, 3]);
+
+  thread::sleep(Duration::from_millis(50));
+  assert!(n.stop().is_empty());
+}
+
+#[test]
+fn zero_interval_doesnt_starve_messages() {
+  let n = TestNode::start(&["n1"], Timers::default);
+
+  n.send(timed("every", 2, 0));
+  assert_eq!(n.recv().body.typ, "every_ok");
+
+  // the interval keeps running, and messages are still handled in between
+  let mut ticks = 0;
+  for id in 3..100 {
+    n.send(msg("c1", request("count", id)));
+    ticks = n.recv().body.value.unwrap().as_u64().unwrap();
+    if ticks > 1 {
+      break;
+    }
+    thread::sleep(Duration::from_millis(5));
+  }
+  assert!(ticks > 1);
+
+  n.send(msg("c1", request("stop", 100)));
+  let stopped = n.recv().body.value.unwrap();
+  thread::sleep(Duration::from_millis(20));
+  n.send(msg("c1", request("count", 101)));
+  assert_eq!(n.recv().body.value.unwrap(), stopped);
+  assert!(n.stop().is_empty());
+}
+
+#[test]
+fn rpc_calls_back_once() {
+  let n = TestNode::start(&["n1"], Timers::default);
+
+  n.send(msg("c1", request("ask", 2)));
+  let ping = n.recv();
+  assert_eq!((ping.dest.as_str(), ping.body.typ.as_str()), ("svc", "ping"));
+  n.send(pong(&ping, 7));
+  let r = n.recv();
+  assert_eq!(r.body.in_reply_to, Some(2));
+  assert_eq!(r.body.value, Some(7.into()));
+
+  // a duplicate reply has nothing waiting for it anymore
+  n.send(pong(&ping, 8));
+  assert!(n.stop().is_empty());
+}
+
+#[test]
+fn rpc_or_timeout_gets_one_answer() {
+  let n = TestNode::start(&["n1"], Timers::default);
+
+  n.send(timed("ask_within", 2, 1_000));
+  let ping = n.recv();
+  n.send(pong(&ping, 7));
+  let r = n.recv();
+  assert_eq!(r.body.typ, "ask_ok");
+  assert_eq!(r.body.value, Some(7.into()));
+
+  n.send(timed("ask_within", 3, 20));
+  let ping = n.recv();
+  let r = n.recv();
+  assert_eq!(r.body.in_reply_to, Some(3));
+  assert_eq!(r.body.code, Some(ErrorCode::Timeout as u64));
+
+  // too late, it's dropped rather than handled
+  n.send(pong(&ping, 8));
+  assert!(n.stop().is_empty());
+}
@@ -0,0 +1,111 @@
+use std::time::{Duration, Instant};
+
+use maelstrom::*;
+use PlumtreeSend::*;
+
+const TIMEOUT: Duration = Duration::from_millis(100);
+
+fn tree() -> Plumtree<u64> {
+  Plumtree::new(vec!["a".into(), "b".into(), "c".into()], TIMEOUT)
+}
+
+#[test]
+fn pushes_to_eager_announces_to_lazy() {
+  let mut t = tree();
+  assert_eq!(
+    t.deliver(1, None),
+    vec![Eager("a".into(), 1), Eager("b".into(), 1), Eager("c".into(), 1)]
+  );
+  // not back to where it came from
+  assert_eq!(
+    t.deliver(2, Some("a")),
+    vec![Eager("b".into(), 2), Eager("c".into(), 2)]
+  );
+
+  t.prune("b");
+  assert_eq!(
+    t.deliver(3, None),
+    vec![Eager("a".into(), 3), Eager("c".into(), 3), IHave("b".into(), 3)]
+  );
+}
+
+#[test]
+fn prunes_redundant_links_once() {
+  let mut t = tree();
+  assert_eq!(t.redundant("a"), vec![Prune("a".into())]);
+  assert_eq!(t.redundant("a"), vec![]);
+  assert_eq!(t.deliver(1, None)[2], IHave("a".into(), 1));
+
+  // a value from a lazy peer puts it back in the tree
+  assert_eq!(
+    t.deliver(2, Some("a")),
+    vec![Eager("b".into(), 2), Eager("c".into(), 2)]
+  );
+  assert_eq!(t.deliver(3, Some("b"))[0], Eager("a".into(), 3));
+}
+
+#[test]
+fn grafts_are_eager_again() {
+  let mut t = tree();
+  t.prune("a");
+  t.prune("c");
+  t.graft("a");
+  assert_eq!(
+    t.deliver(1, None),
+    vec![Eager("a".into(), 1), Eager("b".into(), 1), IHave("c".into(), 1)]
+  );
+}
+
+#[test]
+fn missing_values_are_grafted_after_the_timeout() {
+  let mut t = tree();
+  let now = Instant::now();
+  t.prune("a");
+  t.ihave(1, "a", now);
+  t.ihave(2, "a", now);
+
+  assert_eq!(t.poll(now + TIMEOUT / 2), vec![]);
+  match &t.poll(now + TIMEOUT)[..] {
+    [Graft(peer, values)] => {
+      assert_eq!(peer, "a");
+      let mut values = values.clone();
+      values.sort_unstable();
+      assert_eq!(values, vec![1, 2]);
+    }
+    sends => panic!("expected one graft, got {:?}", sends),
+  }
+  // and pushed to from now on
+  assert_eq!(t.deliver(3, None)[0], Eager("a".into(), 3));
+}
+
+#[test]
+fn grafts_rotate_through_announcers() {
+  let mut t = tree();
+  let now = Instant::now();
+  t.ihave(1, "a", now);
+  t.ihave(1, "b", now);
+  t.ihave(1, "a", now);
+
+  let grafted = |sends: Vec<PlumtreeSend<u64>>| match &sends[..] {
+    [Graft(peer, values)] if *values == vec![1] => peer.clone(),
+    _ => panic!("expected a graft of 1, got {:?}", sends),
+  };
+  assert_eq!(grafted(t.poll(now + TIMEOUT)), "a");
+  // the deadline moved on
+  assert_eq!(t.poll(now + TIMEOUT), vec![]);
+  assert_eq!(grafted(t.poll(now + TIMEOUT * 2)), "b");
+  assert_eq!(grafted(t.poll(now + TIMEOUT * 3)), "a");
+}
+
+#[test]
+fn arrived_values_arent_grafted() {
+  let mut t = tree();
+  let now = Instant::now();
+  t.ihave(1, "a", now);
+  t.ihave(2, "b", now);
+  // e.g. by anti-entropy
+  t.have(&1);
+  t.deliver(2, Some("c"));
+
+  assert_eq!(t.poll(now + TIMEOUT), vec![]);
+}
@@ -0,0 +1,57 @@
+use std::{
+  sync::{mpsc, Arc, Barrier, Mutex},
+  thread,
+  time::Duration,
+};
+
+use maelstrom::*;
+
+#[test]
+fn same_key_runs_in_order() {
+  let pool = WorkerPool::new(4);
+  let seen = Arc::new(Mutex::new(vec![]));
+  for i in 0..50 {
+    let seen = seen.clone();
+    pool.submit(Some("k".to_owned()), move || {
+      // give later jobs a chance to overtake, if they could
+      thread::sleep(Duration::from_micros(100 * (i % 3)));
+      seen.lock().unwrap().push(i);
+    });
+  }
+  pool.join();
+
+  assert_eq!(*seen.lock().unwrap(), (0..50).collect::<Vec<_>>());
+}
+
+#[test]
+fn different_keys_run_at_once() {
+  let pool = WorkerPool::new(2);
+  // each job waits for the other, so this deadlocks unless they run together
+  let barrier = Arc::new(Barrier::new(2));
+  let (tx, rx) = mpsc::channel();
+  for key in ["a", "b"] {
+    let (barrier, tx) = (barrier.clone(), tx.clone());
+    pool.submit(Some(key.to_owned()), move || {
+      barrier.wait();
+      tx.send(()).unwrap();
+    });
+  }
+
+  for _ in 0..2 {
+    rx.recv_timeout(Duration::from_secs(5))
+      .expect("jobs on different keys didn't run concurrently");
+  }
+  pool.join();
+}
+
+#[test]
+fn panics_release_the_key() {
+  let pool = WorkerPool::new(1);
+  let (tx, rx) = mpsc::channel();
+  pool.submit(Some("k".to_owned()), || panic!("job failed"));
+  pool.submit(Some("k".to_owned()), move || tx.send(()).unwrap());
+
+  rx.recv_timeout(Duration::from_secs(5))
+    .expect("the job after a panic on its key didn't run");
+  pool.join();
+}
@@ -0,0 +1,44 @@
+use anyhow::Result;
+use std::env;
+
+use maelstrom::*;
+
+mod common;
+use common::*;
+
+struct Echo;
+
+impl Handler for Echo {
+  fn handle(&mut self, node: &mut Node<Self>, msg: Message) -> Result<()> {
+    node.reply(&msg, body("echo_ok"))
+  }
+}
+
+#[test]
+fn holds_until_init_and_rejects_clients_past_the_limit() {
+  env::set_var("MAELSTROM_PRE_INIT_LIMIT", "2");
+  let n = TestNode::spawn(|| Echo);
+
+  n.send(msg("c1", request("echo", 2)));
+  n.send(msg("c2", request("echo", 3)));
+  // past the limit, the client is told to come back later
+  n.send(msg("c1", request("echo", 4)));
+  let r = n.recv();
+  assert_eq!((r.dest.as_str(), r.body.in_reply_to), ("c1", Some(4)));
+  assert_eq!(r.body.code, Some(ErrorCode::TemporarilyUnavailable as u64));
+
+  // peers' requests and replies are dropped without an answer
+  n.send(msg("n2", request("gossip", 5)));
+  let ack = MsgBody {
+    in_reply_to: Some(9),
+    ..body("gossip_ok")
+  };
+  n.send(msg("n2", ack));
+
+  n.send(init(&["n1", "n2"]));
+  assert_eq!(n.recv().body.typ, "init_ok");
+  let held: Vec<(String, Option<u64>)> = (0..2).map(|_| n.recv()).map(|r| (r.dest, r.body.in_reply_to)).collect();
+  assert_eq!(held, vec![("c1".to_owned(), Some(2)), ("c2".to_owned(), Some(3))]);
+
+  assert!(n.stop().is_empty());
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use std::time::Duration;
+
+use maelstrom::*;
+
+mod common;
+use common::*;
+
+const PEERS: [&str; 3] = ["n2", "n3", "n4"];
+
+fn peers() -> Vec<NodeID> {
+  PEERS.iter().map(|p| p.to_string()).collect()
+}
+
+// asks the peers to `store`, replies `ok` with how many did once a majority
+// have, or the error
+struct Callbacks;
+
+impl Handler for Callbacks {
+  fn handle(&mut self, node: &mut Node<Self>, msg: Message) -> Result<()> {
+    let need = majority(PEERS.len());
+    node.quorum(
+      &peers(),
+      body("store"),
+      need,
+      Duration::from_millis(100),
+      move |_, node, outcome| node.reply(&msg, answer(outcome.map_err(Into::into))),
+    )
+  }
+}
+
+struct Awaits;
+
+impl AsyncHandler for Awaits {
+  async fn handle(&self, ctx: &AsyncContext, msg: Message) -> Result<()> {
+    let outcome = ctx.quorum(&peers(), body("store"), majority(PEERS.len())).await;
+    ctx.reply(&msg, answer(outcome))
+  }
+}
+
+fn answer(outcome: Result<Vec<Message>>) -> MsgBody {
+  match outcome {
+    Ok(replies) => MsgBody {
+      value: Some(replies.len().into()),
+      ..body("ok")
+    },
+    Err(e) => e.downcast::<Error>().unwrap().into(),
+  }
+}
+
+// has the peers answer with `answers`, in order, `None` staying silent
+fn run(n: &TestNode, msg_id: u64, answers: [Option<&str>; 3]) -> Message {
+  n.send(msg("c1", request("req", msg_id)));
+
+  let mut stores: Vec<Message> = (0..3).map(|_| n.recv()).collect();
+  stores.sort_by(|a, b| a.dest.cmp(&b.dest));
+  for (store, answer) in stores.iter().zip(answers) {
+    let reply = match answer {
+      Some("error") => MsgBody::error(ErrorCode::Crash, "down"),
+      Some(typ) => body(typ),
+      None => continue,
+    };
+    n.send(reply_to(store, reply));
+  }
+
+  n.recv()
+}
+
+#[test]
+fn callback_quorum() {
+  let n = TestNode::start(&["n1"], || Callbacks);
+
+  let r = run(&n, 2, [Some("store_ok"), Some("error"), Some("store_ok")]);
+  assert_eq!(r.body.typ, "ok");
+  assert_eq!(r.body.value, Some(2.into()));
+
+  let r = run(&n, 3, [Some("store_ok"), None, Some("error")]);
+  assert_eq!(r.body.code, Some(ErrorCode::Timeout as u64));
+}
+
+#[test]
+fn async_quorum() {
+  let n = TestNode::start(&["n1"], || Async::new(Awaits));
+
+  let r = run(&n, 2, [None, Some("store_ok"), Some("store_ok")]);
+  assert_eq!(r.body.typ, "ok");
+  assert_eq!(r.body.value, Some(2.into()));
+
+  let r = run(&n, 3, [Some("error"), Some("error"), Some("store_ok")]);
+  assert_eq!(r.body.code, Some(ErrorCode::Timeout as u64));
+}
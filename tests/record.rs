@@ -0,0 +1,66 @@
+use anyhow::Result;
+use std::{
+  env, fs,
+  process::{self, Command, Stdio},
+};
+
+use maelstrom::*;
+
+mod common;
+use common::*;
+
+// the same as the echo binary, which the trace is replayed against
+struct Echo;
+
+impl Handler for Echo {
+  fn handle(&mut self, node: &mut Node<Self>, msg: Message) -> Result<()> {
+    let r = MsgBody {
+      echo: msg.body.echo.clone(),
+      ..body("echo_ok")
+    };
+    node.reply(&msg, r)
+  }
+}
+
+#[test]
+fn records_per_node_and_replays() {
+  let dir = env::temp_dir().join(format!("maelstrom-record-{}", process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  env::set_var("MAELSTROM_RECORD", dir.join("{node}.jsonl"));
+
+  let n = TestNode::spawn(|| Echo);
+  // before init, kept until the file can be named
+  let early = MsgBody {
+    echo: Some("early".into()),
+    ..request("echo", 2)
+  };
+  n.send(msg("c1", early));
+  n.send(init(&["n1", "n2"]));
+  for id in 3..6 {
+    let echo = MsgBody {
+      echo: Some(id.into()),
+      ..request("echo", id)
+    };
+    n.send(msg("c2", echo));
+  }
+  assert_eq!(n.stop().len(), 5);
+
+  let trace = dir.join("n1.jsonl");
+  let records = read_trace(&trace).unwrap();
+  assert_eq!(records.len(), 10);
+  assert_eq!(records[0].msg.body.echo, Some("early".into()));
+  assert!(!dir.join("{node}.jsonl").exists());
+
+  let out = Command::new(env!("CARGO_BIN_EXE_replay"))
+    .arg("--fast")
+    .arg(&trace)
+    .arg(env!("CARGO_BIN_EXE_echo"))
+    .stderr(Stdio::null())
+    .output()
+    .unwrap();
+  let summary = String::from_utf8(out.stdout).unwrap();
+  assert!(out.status.success(), "{}", summary);
+  assert!(summary.contains("5 recorded, 5 replayed, 0 differences"), "{}", summary);
+
+  fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+
+use maelstrom::*;
+
+const INITIAL: Duration = Duration::from_millis(100);
+const MAX: Duration = Duration::from_millis(400);
+
+fn msg(msg_id: u64) -> Message {
+  Message {
+    src: "n1".to_owned(),
+    dest: "n2".to_owned(),
+    body: MsgBody {
+      typ: "gossip".to_owned(),
+      msg_id: Some(msg_id),
+      ..Default::default()
+    },
+  }
+}
+
+fn ids(msgs: Vec<Message>) -> Vec<u64> {
+  msgs.iter().map(|m| m.body.msg_id.unwrap()).collect()
+}
+
+#[test]
+fn backs_off_up_to_max() {
+  let mut r = Retransmitter::new(INITIAL, MAX);
+  let t0 = Instant::now();
+  r.track(msg(1), t0);
+
+  assert!(r.poll(t0 + INITIAL / 2).is_empty());
+  // due after 100ms, then 200, 400, 400
+  let mut t = t0;
+  for wait in [100, 200, 400, 400, 400] {
+    let wait = Duration::from_millis(wait);
+    assert!(
+      r.poll(t + wait - Duration::from_millis(1)).is_empty(),
+      "early after {:?}",
+      wait
+    );
+    t += wait;
+    assert_eq!(ids(r.poll(t)), vec![1]);
+  }
+  assert_eq!(r.len(), 1);
+}
+
+#[test]
+fn acks_stop_retries() {
+  let mut r = Retransmitter::new(INITIAL, MAX);
+  let t0 = Instant::now();
+  r.track(msg(1), t0);
+  r.track(msg(2), t0);
+
+  assert_eq!(r.ack(1).map(|m| m.body.msg_id), Some(Some(1)));
+  assert!(r.ack(1).is_none());
+  assert!(r.ack(7).is_none());
+  assert_eq!(ids(r.poll(t0 + INITIAL)), vec![2]);
+
+  r.ack(2);
+  assert!(r.is_empty());
+  assert!(r.poll(t0 + MAX * 10).is_empty());
+}
+
+#[test]
+fn stale_entries_are_skipped() {
+  let mut r = Retransmitter::new(INITIAL, MAX);
+  let t0 = Instant::now();
+  r.track(msg(1), t0);
+  // rescheduled to t0 + 300ms
+  assert_eq!(ids(r.poll(t0 + INITIAL)), vec![1]);
+  // tracked, acked and tracked again with the same id, its first
+  // deadline at t0 + 200ms stays queued
+  r.track(msg(2), t0 + INITIAL);
+  r.ack(2);
+  r.track(msg(2), t0 + INITIAL * 2);
+
+  // each is sent once at t0 + 300ms, 2 not at its stale deadline
+  assert!(r.poll(t0 + INITIAL * 2 + INITIAL / 2).is_empty());
+  let mut due = ids(r.poll(t0 + INITIAL * 3));
+  due.sort_unstable();
+  assert_eq!(due, vec![1, 2]);
+  assert!(r.poll(t0 + INITIAL * 3).is_empty());
+}
@@ -0,0 +1,69 @@
+use std::collections::{HashMap, HashSet};
+
+use maelstrom::*;
+
+fn ring(n: usize) -> HashRing {
+  HashRing::new((1..=n).map(|i| format!("n{}", i)), 64)
+}
+
+fn owners(ring: &HashRing) -> Vec<NodeID> {
+  (0..10_000u64).map(|k| ring.owner(&k).unwrap()).collect()
+}
+
+#[test]
+fn replica_sets() {
+  let r = ring(5);
+  assert_eq!(r.len(), 5);
+
+  for k in 0..100u64 {
+    let replicas = r.owners(&k, 3);
+    assert_eq!(replicas.len(), 3);
+    assert_eq!(replicas.iter().collect::<HashSet<_>>().len(), 3);
+    assert_eq!(Some(&replicas[0]), r.owner(&k).as_ref());
+    // the same on every node
+    assert_eq!(replicas, ring(5).owners(&k, 3));
+  }
+
+  assert_eq!(r.owners("x", 9).len(), 5);
+  assert_eq!(HashRing::new(vec![], 64).owner("x"), None);
+}
+
+#[test]
+fn spreads_keys() {
+  let mut counts: HashMap<NodeID, usize> = HashMap::new();
+  for o in owners(&ring(5)) {
+    *counts.entry(o).or_default() += 1;
+  }
+
+  assert_eq!(counts.len(), 5);
+  // 2_000 each, on average
+  assert!(counts.values().all(|c| (1_000..3_000).contains(c)), "{:?}", counts);
+}
+
+#[test]
+fn minimal_movement() {
+  let mut r = ring(5);
+  let before = owners(&r);
+
+  // only keys taken by the new node move
+  assert!(r.add("n6"));
+  assert!(!r.add("n6"));
+  let after = owners(&r);
+  let moved: Vec<usize> = (0..before.len()).filter(|&k| before[k] != after[k]).collect();
+  assert!(moved.iter().all(|&k| after[k] == "n6"));
+  assert!((500..3_000).contains(&moved.len()), "{} moved", moved.len());
+
+  // and back where they were once it leaves
+  assert!(r.remove("n6"));
+  assert!(!r.contains("n6"));
+  assert_eq!(owners(&r), before);
+
+  // only the keys of a leaving node move
+  r.remove("n3");
+  let after = owners(&r);
+  for k in 0..before.len() {
+    if before[k] != "n3" {
+      assert_eq!(before[k], after[k]);
+    }
+  }
+}
@@ -0,0 +1,79 @@
+use std::{
+  io::Write,
+  process::{Command, Stdio},
+};
+
+mod common;
+use common::*;
+
+#[test]
+fn echo_exits_on_eof() {
+  let out = run_to_eof(
+    env!("CARGO_BIN_EXE_echo"),
+    &[
+      INIT,
+      r#"{"src":"c1","dest":"n1","body":{"type":"echo","msg_id":2,"echo":"hi"}}"#,
+    ],
+  );
+
+  assert_eq!(out.len(), 2);
+  assert!(out[1].contains(r#""echo_ok""#));
+}
+
+#[test]
+fn timers_dont_keep_node_alive() {
+  // g-counter has a replication interval running
+  let out = run_to_eof(
+    env!("CARGO_BIN_EXE_g-counter"),
+    &[
+      INIT,
+      r#"{"src":"c1","dest":"n1","body":{"type":"add","msg_id":2,"delta":3}}"#,
+    ],
+  );
+
+  assert!(out.iter().any(|l| l.contains(r#""add_ok""#)));
+}
+
+#[test]
+fn broadcast_flushes_queued_values_on_shutdown() {
+  let out = run_to_eof(
+    env!("CARGO_BIN_EXE_broadcast"),
+    &[
+      INIT,
+      r#"{"src":"c1","dest":"n1","body":{"type":"topology","msg_id":2,"topology":{"n1":["n2"],"n2":["n1"]}}}"#,
+      r#"{"src":"c1","dest":"n1","body":{"type":"broadcast","msg_id":3,"message":7}}"#,
+    ],
+  );
+
+  assert!(out
+    .iter()
+    .any(|l| l.contains(r#""gossip""#) && l.contains(r#""dest":"n2""#)));
+}
+
+#[test]
+fn exits_when_output_is_closed() {
+  let bin = env!("CARGO_BIN_EXE_echo");
+  let mut child = Command::new(bin)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn()
+    .unwrap();
+  drop(child.stdout.take());
+
+  // stdin stays open, the broken pipe alone should stop it
+  let mut stdin = child.stdin.take().unwrap();
+  writeln!(stdin, "{}", INIT).unwrap();
+  for i in 2..10 {
+    let echo = format!(
+      r#"{{"src":"c1","dest":"n1","body":{{"type":"echo","msg_id":{},"echo":"hi"}}}}"#,
+      i
+    );
+    // it may be gone already
+    if writeln!(stdin, "{}", echo).is_err() {
+      break;
+    }
+  }
+
+  wait_for_exit(&mut child, bin);
+}
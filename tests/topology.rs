@@ -0,0 +1,126 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use maelstrom::*;
+
+fn ids(n: usize) -> Vec<NodeID> {
+  (1..=n).map(|i| format!("n{}", i)).collect()
+}
+
+// every node's neighbors
+fn graph(overlay: Overlay, n: usize, given: Option<&Topology>) -> HashMap<NodeID, Vec<NodeID>> {
+  let nodes = ids(n);
+  nodes
+    .iter()
+    .map(|x| (x.clone(), overlay.neighbors(x, &nodes, given)))
+    .collect()
+}
+
+fn edges(g: &HashMap<NodeID, Vec<NodeID>>) -> usize {
+  g.values().map(Vec::len).sum::<usize>() / 2
+}
+
+// symmetric, without self links or duplicates, and connected
+fn check(overlay: Overlay, g: &HashMap<NodeID, Vec<NodeID>>) {
+  for (x, nbs) in g {
+    assert!(!nbs.contains(x), "{:?}: {} links to itself", overlay, x);
+    assert_eq!(
+      nbs.iter().collect::<HashSet<_>>().len(),
+      nbs.len(),
+      "{:?}: {:?}",
+      overlay,
+      g
+    );
+    for y in nbs {
+      assert!(g[y].contains(x), "{:?}: {} - {} is one way", overlay, x, y);
+    }
+  }
+
+  let mut seen = HashSet::new();
+  let mut queue = VecDeque::from(vec!["n1".to_owned()]);
+  while let Some(x) = queue.pop_front() {
+    if seen.insert(x.clone()) {
+      queue.extend(g[&x].iter().cloned());
+    }
+  }
+  assert_eq!(seen.len(), g.len(), "{:?} isn't connected: {:?}", overlay, g);
+}
+
+#[test]
+fn symmetric_and_connected() {
+  let overlays = [
+    Overlay::Tree(1),
+    Overlay::Tree(2),
+    Overlay::Tree(3),
+    Overlay::Tree(usize::MAX),
+    Overlay::Grid,
+    Overlay::HubRing,
+    Overlay::SpanningTree,
+  ];
+  for overlay in overlays {
+    for n in [1, 2, 3, 5, 7, 10, 25] {
+      let g = graph(overlay, n, None);
+      check(overlay, &g);
+      if matches!(overlay, Overlay::Tree(_) | Overlay::SpanningTree) {
+        assert_eq!(edges(&g), n - 1, "{:?} of {} isn't a tree", overlay, n);
+      }
+    }
+  }
+}
+
+#[test]
+fn layouts() {
+  let g = graph(Overlay::Tree(2), 7, None);
+  assert_eq!(g["n1"], ids(3)[1..]);
+  assert_eq!(g["n2"], vec!["n1", "n4", "n5"]);
+  assert_eq!(g["n7"], vec!["n3"]);
+
+  // 3 columns, n4 n5 on the second row
+  let g = graph(Overlay::Grid, 5, None);
+  assert_eq!(g["n1"], vec!["n2", "n4"]);
+  assert_eq!(g["n3"], vec!["n2"]);
+  assert_eq!(g["n5"], vec!["n4", "n2"]);
+
+  let g = graph(Overlay::HubRing, 5, None);
+  assert_eq!(g["n1"], ids(5)[1..]);
+  assert_eq!(g["n2"], vec!["n1", "n3", "n5"]);
+  assert_eq!(graph(Overlay::HubRing, 2, None)["n2"], vec!["n1"]);
+
+  // star without a topology
+  assert_eq!(graph(Overlay::SpanningTree, 4, None)["n1"], ids(4)[1..]);
+  assert_eq!(graph(Overlay::Tree(usize::MAX), 4, None)["n1"], ids(4)[1..]);
+}
+
+#[test]
+fn spanning_tree_of_given() {
+  // a ring with a chord
+  let mut given = Topology::new();
+  for i in 1..=6usize {
+    let prev = format!("n{}", (i + 4) % 6 + 1);
+    let next = format!("n{}", i % 6 + 1);
+    given.insert(format!("n{}", i), vec![prev, next]);
+  }
+  given.get_mut("n1").unwrap().push("n4".into());
+  given.get_mut("n4").unwrap().push("n1".into());
+
+  let g = graph(Overlay::SpanningTree, 6, Some(&given));
+  check(Overlay::SpanningTree, &g);
+  assert_eq!(edges(&g), 5);
+  for (x, nbs) in &g {
+    assert!(
+      nbs.iter().all(|y| given[x].contains(y)),
+      "{} - {:?} isn't given",
+      x,
+      nbs
+    );
+  }
+  assert_eq!(graph(Overlay::Given, 6, Some(&given)), given);
+}
+
+#[test]
+fn parses() {
+  assert_eq!("tree".parse::<Overlay>().unwrap(), Overlay::Tree(2));
+  assert_eq!("tree:4".parse::<Overlay>().unwrap(), Overlay::Tree(4));
+  assert!("tree:0".parse::<Overlay>().is_err());
+  assert!("ring".parse::<Overlay>().is_err());
+  assert_eq!("hub-ring".parse::<Overlay>().unwrap(), Overlay::HubRing);
+}
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use std::{
+  collections::HashMap,
+  io::{BufRead, BufReader, Write},
+  net::{TcpListener, TcpStream},
+  thread,
+  time::Duration,
+};
+
+use maelstrom::*;
+
+mod common;
+use common::*;
+
+// replies to `echo`, and relays `relay` to the node in `echo`
+struct Echo;
+
+impl Handler for Echo {
+  fn handle(&mut self, node: &mut Node<Self>, msg: Message) -> Result<()> {
+    match msg.body.typ.as_str() {
+      "echo" => {
+        let r = MsgBody {
+          typ: "echo_ok".to_owned(),
+          echo: msg.body.echo.clone(),
+          ..Default::default()
+        };
+        node.reply(&msg, r)
+      }
+      "relay" => {
+        let dest = msg.body.echo.clone().unwrap();
+        let dest = dest.as_str().unwrap();
+        let bd = MsgBody {
+          typ: "echo".to_owned(),
+          echo: Some(node.id.clone().into()),
+          ..Default::default()
+        };
+        node.rpc(dest, bd, move |_, node, resp| {
+          let r = MsgBody {
+            typ: "relay_ok".to_owned(),
+            echo: resp.body.echo,
+            ..Default::default()
+          };
+          node.reply(&msg, r)
+        })
+      }
+      _ => Ok(()),
+    }
+  }
+}
+
+fn parse(line: &str) -> Message {
+  serde_json::from_str(line).unwrap()
+}
+
+#[test]
+fn memory() {
+  let n = TestNode::spawn(|| Echo);
+  n.send(parse(
+    r#"{"src":"c1","dest":"n1","body":{"type":"init","msg_id":1,"node_id":"n1","node_ids":["n1"]}}"#,
+  ));
+  n.send(parse(
+    r#"{"src":"c1","dest":"n1","body":{"type":"echo","msg_id":2,"echo":"hi"}}"#,
+  ));
+
+  let out = n.stop();
+  assert_eq!(out.len(), 2);
+  assert_eq!(out[0].body.typ, "init_ok");
+  assert_eq!(out[1].body.typ, "echo_ok");
+  assert_eq!(out[1].body.echo, Some("hi".into()));
+}
+
+fn free_addr() -> String {
+  TcpListener::bind("127.0.0.1:0")
+    .unwrap()
+    .local_addr()
+    .unwrap()
+    .to_string()
+}
+
+fn start(id: &str, peers: &HashMap<NodeID, String>) {
+  let transport = Tcp::new(id, peers.clone());
+  thread::spawn(move || Node::run_with(Echo, transport));
+}
+
+fn connect(addr: &str) -> TcpStream {
+  for _ in 0..50 {
+    match TcpStream::connect(addr) {
+      Ok(conn) => return conn,
+      Err(_) => thread::sleep(Duration::from_millis(20)),
+    }
+  }
+  panic!("{} isn't listening", addr)
+}
+
+// asks the node to relay an echo through `via`
+fn relay(conn: &mut TcpStream, msg_id: u64, via: &str) {
+  writeln!(
+    conn,
+    r#"{{"src":"c1","dest":"n1","body":{{"type":"relay","msg_id":{},"echo":"{}"}}}}"#,
+    msg_id, via
+  )
+  .unwrap();
+}
+
+#[test]
+fn tcp_cluster() {
+  let peers: HashMap<NodeID, String> = ["n1", "n2"].iter().map(|n| (n.to_string(), free_addr())).collect();
+  for id in ["n1", "n2"] {
+    start(id, &peers);
+  }
+
+  let mut conn = connect(&peers["n1"]);
+  conn.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+  relay(&mut conn, 1, "n2");
+
+  let mut line = String::new();
+  BufReader::new(conn).read_line(&mut line).unwrap();
+  let reply = parse(&line);
+  assert_eq!(reply.src, "n1");
+  assert_eq!(reply.body.typ, "relay_ok");
+  assert_eq!(reply.body.in_reply_to, Some(1));
+  assert_eq!(reply.body.echo, Some("n1".into()));
+}
+
+#[test]
+fn tcp_peers_are_reached_once_up() {
+  let peers: HashMap<NodeID, String> = ["n1", "n2"].iter().map(|n| (n.to_string(), free_addr())).collect();
+  start("n1", &peers);
+  let mut conn = connect(&peers["n1"]);
+  // n2 is down, the relay is dropped and n1 backs off
+  relay(&mut conn, 1, "n2");
+  thread::sleep(Duration::from_millis(50));
+
+  start("n2", &peers);
+  conn.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+  let mut replies = BufReader::new(conn.try_clone().unwrap());
+  for msg_id in 2..12 {
+    relay(&mut conn, msg_id, "n2");
+    let mut line = String::new();
+    if replies.read_line(&mut line).is_ok() {
+      assert_eq!(parse(&line).body.typ, "relay_ok");
+      return;
+    }
+  }
+  panic!("n2 was never reached");
+}
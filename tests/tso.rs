@@ -0,0 +1,62 @@
+use anyhow::Result;
+use std::{cell::RefCell, rc::Rc};
+
+use maelstrom::*;
+
+mod common;
+use common::*;
+
+#[test]
+fn local_is_monotonic() {
+  let tso = LocalTso::default();
+  let seen = Rc::new(RefCell::new(vec![]));
+
+  let mut executor = Executor::default();
+  for _ in 0..3 {
+    let (tso, seen) = (tso.clone(), seen.clone());
+    executor.spawn(async move {
+      for _ in 0..10 {
+        let ts = tso.ts().await.unwrap();
+        seen.borrow_mut().push(ts);
+      }
+    });
+  }
+  executor.run_ready();
+
+  assert!(executor.is_empty());
+  let seen = seen.borrow();
+  assert_eq!(seen.len(), 30);
+  assert!(seen.windows(2).all(|w| w[0] < w[1]));
+}
+
+// replies to `stamp` with a timestamp from lin-tso
+struct Stamp;
+
+impl AsyncHandler for Stamp {
+  async fn handle(&self, ctx: &AsyncContext, msg: Message) -> Result<()> {
+    let r = MsgBody {
+      ts: Some(Tso::new(ctx).ts().await?),
+      ..body("stamp_ok")
+    };
+    ctx.reply(&msg, r)
+  }
+}
+
+#[test]
+fn client_speaks_lin_tso() {
+  let n = TestNode::start(&["n1"], || Async::new(Stamp));
+  n.send(msg("c1", request("stamp", 2)));
+
+  let ts = n.recv();
+  assert_eq!(ts.dest, "lin-tso");
+  assert_eq!(ts.body.typ, "ts");
+  let mut ts_ok = body("ts_ok");
+  ts_ok.ts = Some(42);
+  n.send(reply_to(&ts, ts_ok));
+
+  let r = n.recv();
+  assert_eq!(r.body.typ, "stamp_ok");
+  assert_eq!(r.body.ts, Some(42));
+
+  assert!(n.stop().is_empty());
+}
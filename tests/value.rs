@@ -0,0 +1,107 @@
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+use maelstrom::*;
+
+mod common;
+use common::*;
+
+fn cv(v: Value) -> CanonicalValue {
+  CanonicalValue::from(v)
+}
+
+#[test]
+fn objects_ignore_key_order() {
+  let a: Value = serde_json::from_str(r#"{"a":1,"b":{"c":[1,2],"d":null}}"#).unwrap();
+  let b: Value = serde_json::from_str(r#"{"b":{"d":null,"c":[1,2]},"a":1}"#).unwrap();
+
+  assert_eq!(cv(a.clone()), cv(b.clone()));
+  assert_eq!(hash_of(&cv(a.clone())), hash_of(&cv(b.clone())));
+  assert_eq!([cv(a), cv(b)].iter().collect::<HashSet<_>>().len(), 1);
+  // but not array order
+  assert_ne!(cv(json!([1, 2])), cv(json!([2, 1])));
+}
+
+#[test]
+fn numbers_are_compared_as_written() {
+  assert_ne!(cv(json!(1)), cv(json!(1.0)));
+  assert_ne!(cv(json!(1)), cv(json!("1")));
+  assert_eq!(cv(json!(1.5)), cv(json!(1.5)));
+}
+
+#[test]
+fn round_trips_through_serde() {
+  let v = json!({"z": [1, 1.0, "x"], "a": {"k": true}});
+  let s = serde_json::to_string(&cv(v.clone())).unwrap();
+  let back: CanonicalValue = serde_json::from_str(&s).unwrap();
+  assert_eq!(back, cv(v.clone()));
+  assert_eq!(back.into_value(), v);
+}
+
+// what `field` of the `read_ok` holds
+fn read_back(bin: &str, requests: &[String], field: fn(MsgBody) -> Option<Value>) -> HashSet<CanonicalValue> {
+  let mut input = vec![INIT];
+  input.extend(requests.iter().map(String::as_str));
+  let read = run_to_eof(bin, &input)
+    .iter()
+    .map(|l| serde_json::from_str::<Message>(l).unwrap())
+    .find(|m| m.body.typ == "read_ok")
+    .expect("no read reply");
+  match field(read.body) {
+    Some(Value::Array(xs)) => xs.into_iter().map(CanonicalValue::from).collect(),
+    v => panic!("expected an array, got {:?}", v),
+  }
+}
+
+fn payloads() -> Vec<Value> {
+  vec![
+    json!("hello"),
+    json!({"b": [1, {"c": null}], "a": "x"}),
+    json!(1),
+    json!(1.0),
+  ]
+}
+
+// from `c1` to `n1`
+fn line(msg_id: usize, body: Value) -> String {
+  let mut body = body;
+  body["msg_id"] = json!(msg_id);
+  json!({"src": "c1", "dest": "n1", "body": body}).to_string()
+}
+
+#[test]
+fn broadcast_reads_back_any_payload() {
+  let mut requests: Vec<String> = payloads()
+    .into_iter()
+    .enumerate()
+    .map(|(i, x)| line(i + 2, json!({"type": "broadcast", "message": x})))
+    .collect();
+  // the same object again, keys in another order
+  requests.push(line(
+    10,
+    json!({"type": "broadcast", "message": {"a": "x", "b": [1, {"c": null}]}}),
+  ));
+  requests.push(line(11, json!({"type": "read"})));
+
+  let read = read_back(env!("CARGO_BIN_EXE_broadcast"), &requests, |b| {
+    b.messages.map(Value::Array)
+  });
+  assert_eq!(read, payloads().into_iter().map(CanonicalValue::from).collect());
+}
+
+#[test]
+fn g_set_reads_back_any_payload() {
+  let mut requests: Vec<String> = payloads()
+    .into_iter()
+    .enumerate()
+    .map(|(i, x)| line(i + 2, json!({"type": "add", "element": x})))
+    .collect();
+  requests.push(line(
+    10,
+    json!({"type": "add", "element": {"a": "x", "b": [1, {"c": null}]}}),
+  ));
+  requests.push(line(11, json!({"type": "read"})));
+
+  let read = read_back(env!("CARGO_BIN_EXE_g-set"), &requests, |b| b.value);
+  assert_eq!(read, payloads().into_iter().map(CanonicalValue::from).collect());
+}